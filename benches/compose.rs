@@ -0,0 +1,65 @@
+//! Blending and copying images into a buffer.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use softbuffer::{AlphaMode, Rect, Surface};
+use std::num::NonZeroU32;
+
+const SIZES: [(u32, u32); 2] = [(640, 480), (1920, 1080)];
+
+/// A gradient with varying alpha, so that blending can't take any shortcuts.
+fn pixels(width: u32, height: u32) -> Vec<u32> {
+    (0..height)
+        .flat_map(|y| {
+            (0..width).map(move |x| ((x ^ y) & 0xff) << 24 | (x & 0xff) << 16 | (y & 0xff) << 8)
+        })
+        .collect()
+}
+
+fn compose(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compose");
+
+    for (width, height) in SIZES {
+        let mut surface = Surface::new_headless(
+            NonZeroU32::new(width).unwrap(),
+            NonZeroU32::new(height).unwrap(),
+        )
+        .unwrap();
+        let full = Rect::new(0, 0, width, height).unwrap();
+        let src = pixels(width, height);
+        let id = format!("{width}x{height}");
+        group.throughput(Throughput::Elements(u64::from(width) * u64::from(height)));
+
+        for (name, alpha_mode) in [
+            ("blend_straight", AlphaMode::Straight),
+            ("blend_premultiplied", AlphaMode::Premultiplied),
+        ] {
+            group.bench_function(BenchmarkId::new(name, &id), |b| {
+                let mut buffer = surface.buffer_mut().unwrap();
+                b.iter(|| buffer.blend_rect(&src, alpha_mode, full));
+            });
+        }
+
+        #[cfg(feature = "image")]
+        {
+            let image = image::RgbaImage::from_fn(width, height, |x, y| {
+                let [a, r, g, b] = src[(y * width + x) as usize].to_be_bytes();
+                image::Rgba([r, g, b, a])
+            });
+
+            group.bench_function(BenchmarkId::new("copy_from_image", &id), |b| {
+                let mut buffer = surface.buffer_mut().unwrap();
+                b.iter(|| buffer.copy_from_image(&image, full));
+            });
+
+            group.bench_function(BenchmarkId::new("blend_from_image", &id), |b| {
+                let mut buffer = surface.buffer_mut().unwrap();
+                b.iter(|| buffer.blend_from_image(&image, full));
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, compose);
+criterion_main!(benches);
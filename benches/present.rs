@@ -0,0 +1,54 @@
+//! Presenting on the headless backend, which measures the work softbuffer does itself without
+//! any display server in the way.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use softbuffer::{Rect, Surface};
+use std::num::NonZeroU32;
+
+const SIZES: [(u32, u32); 3] = [(640, 480), (1920, 1080), (3840, 2160)];
+
+fn present(c: &mut Criterion) {
+    let mut group = c.benchmark_group("present");
+
+    for (width, height) in SIZES {
+        let mut surface = Surface::new_headless(
+            NonZeroU32::new(width).unwrap(),
+            NonZeroU32::new(height).unwrap(),
+        )
+        .unwrap();
+        let id = format!("{width}x{height}");
+        let pixels = Throughput::Elements(u64::from(width) * u64::from(height));
+
+        group.throughput(pixels.clone());
+        group.bench_function(BenchmarkId::new("full", &id), |b| {
+            b.iter(|| {
+                let mut buffer = surface.buffer_mut().unwrap();
+                buffer.fill(0x00ff8000);
+                buffer.present().unwrap();
+            });
+        });
+
+        // A typical incremental frame, e.g. a blinking cursor.
+        let damage = Rect::new(width / 2, height / 2, 64, 64).unwrap();
+        group.throughput(Throughput::Elements(64 * 64));
+        group.bench_function(BenchmarkId::new("damage", &id), |b| {
+            b.iter(|| {
+                let buffer = surface.buffer_mut().unwrap();
+                buffer.present_with_damage(&[damage]).unwrap();
+            });
+        });
+
+        group.throughput(pixels);
+        group.bench_function(BenchmarkId::new("present_and_fetch", &id), |b| {
+            b.iter(|| {
+                let buffer = surface.buffer_mut().unwrap();
+                criterion::black_box(buffer.present_and_fetch().unwrap());
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, present);
+criterion_main!(benches);
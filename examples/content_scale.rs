@@ -0,0 +1,126 @@
+//! Toggle between the window's scale factor and a pinned scale of 1 with `Surface::set_content_scale`.
+//!
+//! On a high-DPI display, the contents should switch between sharp and blurry at once. On macOS,
+//! this also checks that changing the scale doesn't fade between the old and new contents.
+
+use std::num::NonZeroU32;
+use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::keyboard::{Key, NamedKey};
+
+#[path = "utils/winit_app.rs"]
+mod winit_app;
+
+/// Draw a checkerboard of single pixels, which shows how sharply the buffer is displayed.
+fn redraw(buffer: &mut [u32], width: usize, height: usize) {
+    for y in 0..height {
+        for x in 0..width {
+            buffer[y * width + x] = if (x + y) % 2 == 0 {
+                0x00ffffff
+            } else {
+                0x00000000
+            };
+        }
+    }
+}
+
+fn main() {
+    let event_loop = EventLoop::new().unwrap();
+
+    let app = winit_app::WinitAppBuilder::with_init(
+        |elwt| {
+            let window = winit_app::make_window(elwt, |w| {
+                w.with_title("Press space to pin/unpin the content scale")
+            });
+
+            let context = softbuffer::Context::new(window.clone()).unwrap();
+
+            let pinned = false;
+
+            (window, context, pinned)
+        },
+        |_elwt, (window, context, _pinned)| {
+            softbuffer::Surface::new(context, window.clone()).unwrap()
+        },
+    )
+    .with_event_handler(|state, surface, event, elwt| {
+        let (window, _context, pinned) = state;
+
+        elwt.set_control_flow(ControlFlow::Wait);
+
+        match event {
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::RedrawRequested,
+            } if window_id == window.id() => {
+                let Some(surface) = surface else {
+                    eprintln!("RedrawRequested fired before Resumed or after Suspended");
+                    return;
+                };
+
+                // With a scale of 1, one pixel of the buffer covers one logical pixel.
+                let size = window.inner_size();
+                let (width, height) = if *pinned {
+                    let size = size.to_logical::<u32>(window.scale_factor());
+                    (size.width, size.height)
+                } else {
+                    (size.width, size.height)
+                };
+                if let (Some(width), Some(height)) =
+                    (NonZeroU32::new(width), NonZeroU32::new(height))
+                {
+                    surface.resize(width, height).unwrap();
+
+                    let mut buffer = surface.buffer_mut().unwrap();
+                    redraw(&mut buffer, width.get() as usize, height.get() as usize);
+                    buffer.present().unwrap();
+                }
+            }
+
+            Event::WindowEvent {
+                event:
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                logical_key: Key::Named(NamedKey::Escape),
+                                ..
+                            },
+                        ..
+                    },
+                window_id,
+            } if window_id == window.id() => {
+                elwt.exit();
+            }
+
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                state: ElementState::Pressed,
+                                logical_key: Key::Named(NamedKey::Space),
+                                ..
+                            },
+                        ..
+                    },
+                window_id,
+            } if window_id == window.id() => {
+                let Some(surface) = surface else {
+                    return;
+                };
+
+                *pinned = !*pinned;
+                let scale = if *pinned { Some(1.0) } else { None };
+                if let Err(err) = surface.set_content_scale(scale) {
+                    eprintln!("failed to set the content scale: {err}");
+                }
+                window.request_redraw();
+            }
+
+            _ => {}
+        }
+    });
+
+    winit_app::run_app(event_loop, app);
+}
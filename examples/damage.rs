@@ -0,0 +1,154 @@
+//! Redraw only the parts of the window that changed, using `Buffer::stale_rects`.
+
+use softbuffer::{Rect, ResizeOutcome};
+use std::num::NonZeroU32;
+use web_time::Instant;
+use winit::event::{Event, KeyEvent, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::keyboard::{Key, NamedKey};
+
+#[path = "utils/winit_app.rs"]
+mod winit_app;
+
+const SQUARE_SIZE: u32 = 100;
+
+/// The square moving back and forth across the window, clipped to the window.
+fn square(elapsed: f64, width: u32, height: u32) -> Option<Rect> {
+    let range = width.saturating_sub(SQUARE_SIZE) as f64;
+    let phase = elapsed % 2.0;
+    let x = (if phase < 1.0 { phase } else { 2.0 - phase } * range) as u32;
+    let y = height.saturating_sub(SQUARE_SIZE) / 2;
+    Rect::from_bounds(
+        x,
+        y,
+        (x + SQUARE_SIZE).min(width),
+        (y + SQUARE_SIZE).min(height),
+    )
+}
+
+/// Draw the part of the scene that lies within `rect`.
+fn draw(buffer: &mut [u32], width: u32, rect: Rect, square: Option<Rect>) {
+    let inside = |x: u32, y: u32| {
+        square.is_some_and(|s| {
+            (s.x..s.x + s.width.get()).contains(&x) && (s.y..s.y + s.height.get()).contains(&y)
+        })
+    };
+
+    for y in rect.y..rect.y + rect.height.get() {
+        for x in rect.x..rect.x + rect.width.get() {
+            let value = if inside(x, y) {
+                0x00ffffff
+            } else {
+                let red = (x & 0xff) ^ (y & 0xff);
+                let green = (x & 0x7f) ^ (y & 0x7f);
+                let blue = (x & 0x3f) ^ (y & 0x3f);
+                blue | (green << 8) | (red << 16)
+            };
+            buffer[y as usize * width as usize + x as usize] = value;
+        }
+    }
+}
+
+fn main() {
+    let event_loop = EventLoop::new().unwrap();
+    let start = Instant::now();
+
+    let app = winit_app::WinitAppBuilder::with_init(
+        |elwt| {
+            let window = winit_app::make_window(elwt, |w| w);
+
+            let context = softbuffer::Context::new(window.clone()).unwrap();
+            let old_square: Option<Rect> = None;
+
+            (window, context, old_square)
+        },
+        |_elwt, (window, context, _old_square)| {
+            softbuffer::Surface::new(context, window.clone()).unwrap()
+        },
+    )
+    .with_event_handler(move |state, surface, event, elwt| {
+        let (window, _context, old_square) = state;
+
+        elwt.set_control_flow(ControlFlow::Poll);
+
+        match event {
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(size),
+            } if window_id == window.id() => {
+                let Some(surface) = surface else {
+                    eprintln!("Resized fired before Resumed or after Suspended");
+                    return;
+                };
+
+                if let (Some(width), Some(height)) =
+                    (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+                {
+                    // The square only has to be redrawn everywhere if the size changed.
+                    if surface.resize(width, height).unwrap() != ResizeOutcome::Unchanged {
+                        *old_square = None;
+                    }
+                }
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::RedrawRequested,
+            } if window_id == window.id() => {
+                let Some(surface) = surface else {
+                    eprintln!("RedrawRequested fired before Resumed or after Suspended");
+                    return;
+                };
+
+                let size = window.inner_size();
+                if let (Some(width), Some(height)) =
+                    (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+                {
+                    let (width, height) = (width.get(), height.get());
+                    let new_square = square(start.elapsed().as_secs_f64(), width, height);
+
+                    // The square has to be erased at its old position and drawn at the new one.
+                    let damage: Vec<Rect> = old_square.iter().chain(&new_square).copied().collect();
+                    *old_square = new_square;
+
+                    let mut buffer = surface.buffer_mut().unwrap();
+                    match buffer.stale_rects() {
+                        Some(stale) => {
+                            // The buffer holds an older frame, only bring the changed parts up
+                            // to date.
+                            for &rect in stale.iter().chain(&damage) {
+                                draw(&mut buffer, width, rect, new_square);
+                            }
+                            buffer.present_with_damage(&damage).unwrap();
+                        }
+                        None => {
+                            let full = Rect::new(0, 0, width, height).unwrap();
+                            draw(&mut buffer, width, full, new_square);
+                            buffer.present().unwrap();
+                        }
+                    }
+                }
+            }
+            Event::AboutToWait => {
+                window.request_redraw();
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        event:
+                            KeyEvent {
+                                logical_key: Key::Named(NamedKey::Escape),
+                                ..
+                            },
+                        ..
+                    },
+                window_id,
+            } if window_id == window.id() => {
+                elwt.exit();
+            }
+            _ => {}
+        }
+    });
+
+    winit_app::run_app(event_loop, app);
+}
@@ -1,11 +1,13 @@
 //! Implements `buffer_interface::*` traits for enums dispatching to backends
 
-use crate::{backend_interface::*, backends, InitError, Rect, SoftBufferError};
+use crate::{backend_interface::*, backends, InitError, PresentStats, Rect, SoftBufferError};
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use std::mem::MaybeUninit;
 use std::num::NonZeroU32;
 #[cfg(any(wayland_platform, x11_platform, kms_platform))]
 use std::sync::Arc;
+use std::time::Duration;
 
 /// A macro for creating the enum used to statically dispatch to the platform-specific implementation.
 macro_rules! make_dispatch {
@@ -17,6 +19,7 @@ macro_rules! make_dispatch {
             ($context_inner: ty, $surface_inner: ty, $buffer_inner: ty),
         )*
     ) => {
+        #[derive(Clone)]
         pub(crate) enum ContextDispatch<$dgen> {
             $(
                 $(#[$attr])*
@@ -53,6 +56,15 @@ macro_rules! make_dispatch {
 
                 Err(InitError::Unsupported(display))
             }
+
+            fn display(&self) -> &D {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.display(),
+                    )*
+                }
+            }
         }
 
         #[allow(clippy::large_enum_variant)] // it's boxed anyways
@@ -63,6 +75,17 @@ macro_rules! make_dispatch {
             )*
         }
 
+        impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceDispatch<D, W> {
+            pub fn variant_name(&self) -> &'static str {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(_) => stringify!($name),
+                    )*
+                }
+            }
+        }
+
         impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for SurfaceDispatch<D, W> {
             type Context = ContextDispatch<D>;
             type Buffer<'a> = BufferDispatch<'a, D, W> where Self: 'a;
@@ -88,6 +111,51 @@ macro_rules! make_dispatch {
                 }
             }
 
+            fn replace_window(&mut self, window: W) -> Result<(), InitError<W>> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.replace_window(window),
+                    )*
+                }
+            }
+
+            fn supports_partial_present(&self) -> bool {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.supports_partial_present(),
+                    )*
+                }
+            }
+
+            fn buffer_count(&self) -> u8 {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.buffer_count(),
+                    )*
+                }
+            }
+
+            fn set_buffer_count(&mut self, count: u8) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_buffer_count(count),
+                    )*
+                }
+            }
+
+            fn uses_shared_memory(&self) -> bool {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.uses_shared_memory(),
+                    )*
+                }
+            }
+
             fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
                 match self {
                     $(
@@ -97,6 +165,155 @@ macro_rules! make_dispatch {
                 }
             }
 
+            fn resize_with_stride(
+                &mut self,
+                width: NonZeroU32,
+                height: NonZeroU32,
+                stride: NonZeroU32,
+            ) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.resize_with_stride(width, height, stride),
+                    )*
+                }
+            }
+
+            fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.maximum_size(),
+                    )*
+                }
+            }
+
+            fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.size(),
+                    )*
+                }
+            }
+
+            fn resize_preserving(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.resize_preserving(width, height),
+                    )*
+                }
+            }
+
+            fn wait_for_vblank(&mut self) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.wait_for_vblank(),
+                    )*
+                }
+            }
+
+            fn set_alpha_blending(&mut self, enabled: bool) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_alpha_blending(enabled),
+                    )*
+                }
+            }
+
+            fn set_opaque_region(&mut self, rects: &[Rect]) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_opaque_region(rects),
+                    )*
+                }
+            }
+
+            fn set_buffer_scale(&mut self, scale: i32) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_buffer_scale(scale),
+                    )*
+                }
+            }
+
+            fn set_content_scale(&mut self, scale: Option<f64>) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_content_scale(scale),
+                    )*
+                }
+            }
+
+            fn set_vertical_flip(&mut self, flip: bool) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_vertical_flip(flip),
+                    )*
+                }
+            }
+
+            fn set_present_timeout(&mut self, timeout: Option<Duration>) {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_present_timeout(timeout),
+                    )*
+                }
+            }
+
+            fn set_present_stats(&mut self, enabled: bool) {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.set_present_stats(enabled),
+                    )*
+                }
+            }
+
+            fn last_present_stats(&self) -> Option<PresentStats> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.last_present_stats(),
+                    )*
+                }
+            }
+
+            fn present_tile(&mut self, pixels: &[u32], tile: Rect) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.present_tile(pixels, tile),
+                    )*
+                }
+            }
+
+            fn flush(&mut self) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.flush(),
+                    )*
+                }
+            }
+
+            fn buffer_ready(&mut self) -> Result<bool, SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.buffer_ready(),
+                    )*
+                }
+            }
+
             fn buffer_mut(&mut self) -> Result<BufferDispatch<'_, D, W>, SoftBufferError> {
                 match self {
                     $(
@@ -106,11 +323,30 @@ macro_rules! make_dispatch {
                 }
             }
 
-            fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
+            unsafe fn buffer_mut_uninit(&mut self) -> Result<BufferDispatch<'_, D, W>, SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        // SAFETY: Upheld by the caller.
+                        Self::$name(inner) => Ok(BufferDispatch::$name(unsafe { inner.buffer_mut_uninit()? })),
+                    )*
+                }
+            }
+
+            fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.fetch_into(out),
+                    )*
+                }
+            }
+
+            fn fetch_rect(&mut self, rect: Rect) -> Result<Vec<u32>, SoftBufferError> {
                 match self {
                     $(
                         $(#[$attr])*
-                        Self::$name(inner) => inner.fetch(),
+                        Self::$name(inner) => inner.fetch_rect(rect),
                     )*
                 }
             }
@@ -124,6 +360,36 @@ macro_rules! make_dispatch {
         }
 
         impl<'a, D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferDispatch<'a, D, W> {
+            #[inline]
+            fn width(&self) -> NonZeroU32 {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.width(),
+                    )*
+                }
+            }
+
+            #[inline]
+            fn height(&self) -> NonZeroU32 {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.height(),
+                    )*
+                }
+            }
+
+            #[inline]
+            fn stride(&self) -> NonZeroU32 {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.stride(),
+                    )*
+                }
+            }
+
             #[inline]
             fn pixels(&self) -> &[u32] {
                 match self {
@@ -144,6 +410,26 @@ macro_rules! make_dispatch {
                 }
             }
 
+            #[inline]
+            fn pixels_uninit(&self) -> &[MaybeUninit<u32>] {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.pixels_uninit(),
+                    )*
+                }
+            }
+
+            #[inline]
+            fn pixels_uninit_mut(&mut self) -> &mut [MaybeUninit<u32>] {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.pixels_uninit_mut(),
+                    )*
+                }
+            }
+
             fn age(&self) -> u8 {
                 match self {
                     $(
@@ -170,6 +456,24 @@ macro_rules! make_dispatch {
                     )*
                 }
             }
+
+            fn present_with_damage_reported(self, damage: &[Rect]) -> Result<Vec<Rect>, SoftBufferError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.present_with_damage_reported(damage),
+                    )*
+                }
+            }
+
+            fn present_and_fetch(self) -> Result<Vec<u32>, PresentAndFetchError> {
+                match self {
+                    $(
+                        $(#[$attr])*
+                        Self::$name(inner) => inner.present_and_fetch(),
+                    )*
+                }
+            }
         }
     };
 }
@@ -194,4 +498,6 @@ make_dispatch! {
     Web(backends::web::WebDisplayImpl<D>, backends::web::WebImpl<D, W>, backends::web::BufferImpl<'a, D, W>),
     #[cfg(target_os = "redox")]
     Orbital(D, backends::orbital::OrbitalImpl<D, W>, backends::orbital::BufferImpl<'a, D, W>),
+    #[cfg(feature = "headless")]
+    Headless(backends::headless::HeadlessContext<D>, backends::headless::HeadlessImpl<D, W>, backends::headless::BufferImpl<'a, D, W>),
 }
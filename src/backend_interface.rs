@@ -1,15 +1,20 @@
 //! Interface implemented by backends
 
-use crate::{InitError, Rect, SoftBufferError};
+use crate::{InitError, PresentStats, Rect, SoftBufferError};
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use std::mem::MaybeUninit;
 use std::num::NonZeroU32;
+use std::slice;
+use std::time::Duration;
 
 pub(crate) trait ContextInterface<D: HasDisplayHandle + ?Sized> {
     fn new(display: D) -> Result<Self, InitError<D>>
     where
         D: Sized,
         Self: Sized;
+    /// Get the display handle that the context was created from.
+    fn display(&self) -> &D;
 }
 
 pub(crate) trait SurfaceInterface<D: HasDisplayHandle + ?Sized, W: HasWindowHandle + ?Sized> {
@@ -24,20 +29,179 @@ pub(crate) trait SurfaceInterface<D: HasDisplayHandle + ?Sized, W: HasWindowHand
         Self: Sized;
     /// Get the inner window handle.
     fn window(&self) -> &W;
+    /// Rebind the surface to a new window, keeping the size.
+    fn replace_window(&mut self, _window: W) -> Result<(), InitError<W>>
+    where
+        W: Sized,
+    {
+        Err(SoftBufferError::Unimplemented.into())
+    }
+    /// Whether the buffer is shared with the display server, so presenting doesn't copy it.
+    fn uses_shared_memory(&self) -> bool {
+        false
+    }
+    /// How many buffers are cycled through, which is the largest age a buffer can have.
+    fn buffer_count(&self) -> u8 {
+        1
+    }
+    /// Change how many buffers are cycled through.
+    fn set_buffer_count(&mut self, count: u8) -> Result<(), SoftBufferError> {
+        if count == self.buffer_count() {
+            Ok(())
+        } else {
+            Err(SoftBufferError::Unimplemented)
+        }
+    }
+    /// Whether presenting with damage only updates the damaged parts of the window.
+    fn supports_partial_present(&self) -> bool {
+        false
+    }
     /// Resize the internal buffer to the given width and height.
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError>;
+    /// Resize the internal buffer, with `stride` pixels per row.
+    fn resize_with_stride(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+        stride: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        if stride == width {
+            self.resize(width, height)
+        } else {
+            Err(SoftBufferError::Unimplemented)
+        }
+    }
+    /// The largest size that `resize` accepts.
+    fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+        (NonZeroU32::MAX, NonZeroU32::MAX)
+    }
+    /// The size that was last set with `resize`, if any.
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)>;
+    /// Resize the internal buffer, keeping the contents that still fit if they were presented.
+    fn resize_preserving(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        self.resize(width, height)
+    }
+    /// Block until the last presented buffer is shown on screen.
+    fn wait_for_vblank(&mut self) -> Result<(), SoftBufferError> {
+        Ok(())
+    }
+    /// Blend the presented buffer with whatever is behind the window, using its alpha channel.
+    fn set_alpha_blending(&mut self, _enabled: bool) -> Result<(), SoftBufferError> {
+        Err(SoftBufferError::Unimplemented)
+    }
+    /// Tell the compositor which parts of the surface are opaque.
+    fn set_opaque_region(&mut self, _rects: &[Rect]) -> Result<(), SoftBufferError> {
+        Err(SoftBufferError::Unimplemented)
+    }
+    /// Set the ratio between the size of the buffer and the size of the surface.
+    fn set_buffer_scale(&mut self, _scale: i32) -> Result<(), SoftBufferError> {
+        Err(SoftBufferError::Unimplemented)
+    }
+    /// Pin the scale of the presented contents, or restore the automatic scale with `None`.
+    fn set_content_scale(&mut self, _scale: Option<f64>) -> Result<(), SoftBufferError> {
+        Err(SoftBufferError::Unimplemented)
+    }
+    /// Show the rows of the buffer bottom to top.
+    fn set_vertical_flip(&mut self, _flip: bool) -> Result<(), SoftBufferError> {
+        Err(SoftBufferError::Unimplemented)
+    }
+    /// Limit how long `buffer_mut` may block waiting for the display server.
+    fn set_present_timeout(&mut self, _timeout: Option<Duration>) {}
+    /// Measure how long `buffer_mut` waits for the display server.
+    fn set_present_stats(&mut self, _enabled: bool) {}
+    /// The measurements of the last `buffer_mut`, if they are turned on.
+    fn last_present_stats(&self) -> Option<PresentStats> {
+        None
+    }
+    /// Present `pixels` in the `tile` region of the window, without touching the buffer.
+    fn present_tile(&mut self, _pixels: &[u32], _tile: Rect) -> Result<(), SoftBufferError> {
+        Err(SoftBufferError::Unimplemented)
+    }
+    /// Send any requests that are still buffered to the display server.
+    fn flush(&mut self) -> Result<(), SoftBufferError> {
+        Ok(())
+    }
+    /// Whether `buffer_mut` can return a buffer without blocking.
+    fn buffer_ready(&mut self) -> Result<bool, SoftBufferError> {
+        Ok(true)
+    }
     /// Get a mutable reference to the buffer.
     fn buffer_mut(&mut self) -> Result<Self::Buffer<'_>, SoftBufferError>;
+    /// Get a mutable reference to a buffer whose contents may be uninitialized.
+    ///
+    /// # Safety
+    ///
+    /// Every pixel has to be written through [`BufferInterface::pixels_uninit_mut`] before any
+    /// other method of the buffer is used.
+    unsafe fn buffer_mut_uninit(&mut self) -> Result<Self::Buffer<'_>, SoftBufferError> {
+        self.buffer_mut()
+    }
+    /// Fetch the buffer from the window into `out`, replacing its contents.
+    fn fetch_into(&mut self, _out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+        Err(SoftBufferError::Unimplemented)
+    }
     /// Fetch the buffer from the window.
     fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
+        let mut out = Vec::new();
+        self.fetch_into(&mut out)?;
+        Ok(out)
+    }
+    /// Fetch a part of the buffer from the window. The rectangle has already been validated.
+    fn fetch_rect(&mut self, _rect: Rect) -> Result<Vec<u32>, SoftBufferError> {
         Err(SoftBufferError::Unimplemented)
     }
 }
 
 pub(crate) trait BufferInterface {
+    fn width(&self) -> NonZeroU32;
+    fn height(&self) -> NonZeroU32;
+    /// The number of pixels in each row of the buffer, including padding.
+    fn stride(&self) -> NonZeroU32 {
+        self.width()
+    }
     fn pixels(&self) -> &[u32];
     fn pixels_mut(&mut self) -> &mut [u32];
+    fn pixels_uninit(&self) -> &[MaybeUninit<u32>] {
+        let pixels = self.pixels();
+        // SAFETY: `MaybeUninit<u32>` has the same layout as `u32`.
+        unsafe { slice::from_raw_parts(pixels.as_ptr().cast(), pixels.len()) }
+    }
+    fn pixels_uninit_mut(&mut self) -> &mut [MaybeUninit<u32>] {
+        let pixels = self.pixels_mut();
+        // SAFETY: `MaybeUninit<u32>` has the same layout as `u32`. The callers of
+        // `buffer_mut_uninit` only write initialized values through this.
+        unsafe { slice::from_raw_parts_mut(pixels.as_mut_ptr().cast(), pixels.len()) }
+    }
     fn age(&self) -> u8;
     fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError>;
+    /// Present the buffer, and return the damage that was actually sent to the display server.
+    fn present_with_damage_reported(self, damage: &[Rect]) -> Result<Vec<Rect>, SoftBufferError>
+    where
+        Self: Sized,
+    {
+        self.present_with_damage(damage)?;
+        Ok(damage.to_vec())
+    }
     fn present(self) -> Result<(), SoftBufferError>;
+    /// Present the buffer, then fetch the window contents once they include it.
+    fn present_and_fetch(self) -> Result<Vec<u32>, PresentAndFetchError>
+    where
+        Self: Sized,
+    {
+        self.present().map_err(PresentAndFetchError::Present)?;
+        Err(PresentAndFetchError::Fetch(SoftBufferError::Unimplemented))
+    }
+}
+
+/// The step of [`BufferInterface::present_and_fetch`] that failed.
+#[derive(Debug)]
+pub(crate) enum PresentAndFetchError {
+    /// The buffer wasn't presented.
+    Present(SoftBufferError),
+    /// The buffer was presented, but the window contents couldn't be fetched.
+    Fetch(SoftBufferError),
 }
@@ -5,18 +5,20 @@ use std::num::{NonZeroI32, NonZeroU32};
 
 use ndk::{
     hardware_buffer_format::HardwareBufferFormat,
-    native_window::{NativeWindow, NativeWindowBufferLockGuard},
+    native_window::{self, NativeWindow},
 };
 #[cfg(doc)]
 use raw_window_handle::AndroidNdkWindowHandle;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 
 use crate::error::InitError;
-use crate::{BufferInterface, Rect, SoftBufferError, SurfaceInterface};
+use crate::{util, BufferInterface, Rect, SoftBufferError, SurfaceInterface};
 
 /// The handle to a window for software buffering.
 pub struct AndroidImpl<D, W> {
     native_window: NativeWindow,
+    /// The size that was last set with `resize`.
+    size: Option<(NonZeroU32, NonZeroU32)>,
     window: W,
     _display: PhantomData<D>,
 }
@@ -41,6 +43,7 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Android
 
         Ok(Self {
             native_window,
+            size: None,
             _display: PhantomData,
             window,
         })
@@ -51,8 +54,44 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Android
         &self.window
     }
 
+    fn buffer_count(&self) -> u8 {
+        // Every buffer is new.
+        0
+    }
+
+    fn supports_partial_present(&self) -> bool {
+        true
+    }
+
+    fn replace_window(&mut self, window: W) -> Result<(), InitError<W>> {
+        let raw = window.window_handle()?.as_raw();
+        let RawWindowHandle::AndroidNdk(a) = raw else {
+            return Err(InitError::Unsupported(window));
+        };
+
+        // SAFETY: We have confirmed that the window handle is valid.
+        self.native_window = unsafe { NativeWindow::clone_from_ptr(a.a_native_window.cast()) };
+        self.window = window;
+
+        // The geometry and format are properties of the `ANativeWindow`, so set them again.
+        if let Some((width, height)) = self.size {
+            self.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+        let max = NonZeroU32::new(i32::MAX as u32).unwrap();
+        (max, max)
+    }
+
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        self.size
+    }
+
     /// Also changes the pixel format to [`HardwareBufferFormat::R8G8B8A8_UNORM`].
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
+        let size = (width, height);
         let (width, height) = (|| {
             let width = NonZeroI32::try_from(width).ok()?;
             let height = NonZeroI32::try_from(height).ok()?;
@@ -72,19 +111,17 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Android
                     Some("Failed to set buffer geometry on ANativeWindow".to_owned()),
                     Some(Box::new(err)),
                 )
-            })
+            })?;
+
+        self.size = Some(size);
+        Ok(())
     }
 
+    /// The window is only locked when presenting, because Android needs to know the damage at
+    /// lock time.
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
-        let native_window_buffer = self.native_window.lock(None).map_err(|err| {
-            SoftBufferError::PlatformError(
-                Some("Failed to lock ANativeWindow".to_owned()),
-                Some(Box::new(err)),
-            )
-        })?;
-
         if !matches!(
-            native_window_buffer.format(),
+            self.native_window.format(),
             // These are the only formats we support
             HardwareBufferFormat::R8G8B8A8_UNORM | HardwareBufferFormat::R8G8B8X8_UNORM
         ) {
@@ -92,37 +129,57 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Android
                 Some(format!(
                     "Unexpected buffer format {:?}, please call \
                     .resize() first to change it to RGBx8888",
-                    native_window_buffer.format()
+                    self.native_window.format()
                 )),
                 None,
             ));
         }
 
-        let buffer = vec![0; native_window_buffer.width() * native_window_buffer.height()];
+        let width = NonZeroU32::new(self.native_window.width() as u32).ok_or_else(|| {
+            SoftBufferError::PlatformError(Some("Window has no width".into()), None)
+        })?;
+        let height = NonZeroU32::new(self.native_window.height() as u32).ok_or_else(|| {
+            SoftBufferError::PlatformError(Some("Window has no height".into()), None)
+        })?;
+        let buffer = vec![0; width.get() as usize * height.get() as usize];
 
         Ok(BufferImpl {
-            native_window_buffer,
+            native_window: &self.native_window,
             buffer,
+            width,
+            height,
             marker: PhantomData,
         })
     }
 
     /// Fetch the buffer from the window.
-    fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
+    fn fetch_into(&mut self, _out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
         Err(SoftBufferError::Unimplemented)
     }
 }
 
 pub struct BufferImpl<'a, D: ?Sized, W> {
-    native_window_buffer: NativeWindowBufferLockGuard<'a>,
+    native_window: &'a NativeWindow,
     buffer: Vec<u32>,
+    width: NonZeroU32,
+    height: NonZeroU32,
     marker: PhantomData<(&'a D, &'a W)>,
 }
 
-// TODO: Move to NativeWindowBufferLockGuard?
+// SAFETY: The buffer only borrows the `NativeWindow`, which is thread safe.
 unsafe impl<'a, D, W> Send for BufferImpl<'a, D, W> {}
 
 impl<'a, D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'a, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        self.height
+    }
+
     #[inline]
     fn pixels(&self) -> &[u32] {
         &self.buffer
@@ -138,35 +195,79 @@ impl<'a, D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl
         0
     }
 
-    // TODO: This function is pretty slow this way
-    fn present(mut self) -> Result<(), SoftBufferError> {
-        let input_lines = self.buffer.chunks(self.native_window_buffer.width());
-        for (output, input) in self
-            .native_window_buffer
+    fn present(self) -> Result<(), SoftBufferError> {
+        let (width, height) = (self.width, self.height);
+        self.present_with_damage(&[Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }])
+    }
+
+    fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError> {
+        self.present_with_damage_reported(damage).map(drop)
+    }
+
+    fn present_with_damage_reported(self, damage: &[Rect]) -> Result<Vec<Rect>, SoftBufferError> {
+        let Some(union) = util::union_damage(damage) else {
+            // Nothing to present.
+            return Ok(Vec::new());
+        };
+
+        // Android may enlarge the dirty region, e.g. when it can't preserve the previous contents
+        // of the window, so it has to be locked before we know what to copy.
+        let mut dirty = native_window::Rect {
+            left: union.x as i32,
+            top: union.y as i32,
+            right: (union.x + union.width.get()) as i32,
+            bottom: (union.y + union.height.get()) as i32,
+        };
+        let mut native_window_buffer =
+            self.native_window.lock(Some(&mut dirty)).map_err(|err| {
+                SoftBufferError::PlatformError(
+                    Some("Failed to lock ANativeWindow".to_owned()),
+                    Some(Box::new(err)),
+                )
+            })?;
+
+        let width = self.width.get() as usize;
+        let clamp = |value: i32, max: usize| (value.max(0) as usize).min(max);
+        let (left, right) = (clamp(dirty.left, width), clamp(dirty.right, width));
+        let (top, bottom) = (
+            clamp(dirty.top, self.height.get() as usize),
+            clamp(dirty.bottom, self.height.get() as usize),
+        );
+
+        let input_lines = self.buffer.chunks(width);
+        for (output, input) in native_window_buffer
             .lines()
             // Unreachable as we checked before that this is a valid, mappable format
             .unwrap()
             .zip(input_lines)
+            .take(bottom)
+            .skip(top)
         {
             // .lines() removed the stride
             assert_eq!(output.len(), input.len() * 4);
 
-            for (i, pixel) in input.iter().enumerate() {
+            // Only the dirty region is copied, Android keeps the rest.
+            for i in left..right {
                 // Swizzle colors from RGBX to BGR
-                let [b, g, r, _] = pixel.to_le_bytes();
+                let [b, g, r, _] = input[i].to_le_bytes();
                 output[i * 4].write(b);
                 output[i * 4 + 1].write(g);
                 output[i * 4 + 2].write(r);
                 // TODO alpha?
             }
         }
-        Ok(())
-    }
 
-    fn present_with_damage(self, _damage: &[Rect]) -> Result<(), SoftBufferError> {
-        // TODO: Android requires the damage rect _at lock time_
-        // Since we're faking the backing buffer _anyway_, we could even fake the surface lock
-        // and lock it here (if it doesn't influence timings).
-        self.present()
+        // Dropping the guard posts the buffer to the window.
+        drop(native_window_buffer);
+        Ok(
+            Rect::from_bounds(left as u32, top as u32, right as u32, bottom as u32)
+                .into_iter()
+                .collect(),
+        )
     }
 }
@@ -21,16 +21,21 @@ use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 
 use std::ffi::c_void;
 use std::marker::PhantomData;
+use std::mem::MaybeUninit;
 use std::num::NonZeroU32;
 use std::ops::Deref;
 use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-struct Buffer(Vec<u32>);
+/// The pixels of a frame, which may start out uninitialized (see `buffer_mut_uninit`).
+struct Buffer(Vec<MaybeUninit<u32>>);
 
 impl AsRef<[u8]> for Buffer {
     fn as_ref(&self) -> &[u8] {
-        bytemuck::cast_slice(&self.0)
+        // SAFETY: Buffers are only presented once all pixels have been written.
+        unsafe { slice::from_raw_parts(self.0.as_ptr().cast(), self.0.len() * 4) }
     }
 }
 
@@ -44,7 +49,7 @@ declare_class!(
     }
 
     impl DeclaredClass for Observer {
-        type Ivars = Retained<CALayer>;
+        type Ivars = ObserverIvars;
     }
 
     // NSKeyValueObserving
@@ -62,13 +67,23 @@ declare_class!(
     }
 );
 
+struct ObserverIvars {
+    /// The layer to keep in sync with the root layer.
+    layer: Retained<CALayer>,
+    /// Whether the user pinned `contentsScale` with `set_content_scale`.
+    scale_pinned: AtomicBool,
+}
+
 // SAFETY: The `CALayer` that the observer contains is thread safe.
 unsafe impl Send for Observer {}
 unsafe impl Sync for Observer {}
 
 impl Observer {
     fn new(layer: &CALayer) -> Retained<Self> {
-        let this = Self::alloc().set_ivars(layer.retain());
+        let this = Self::alloc().set_ivars(ObserverIvars {
+            layer: layer.retain(),
+            scale_pinned: AtomicBool::new(false),
+        });
         unsafe { msg_send_id![super(this), init] }
     }
 
@@ -77,7 +92,7 @@ impl Observer {
         key_path: Option<&NSString>,
         change: Option<&NSDictionary<NSKeyValueChangeKey, AnyObject>>,
     ) {
-        let layer = self.ivars();
+        let layer = &self.ivars().layer;
 
         let change =
             change.expect("requested a change dictionary in `addObserver`, but none was provided");
@@ -88,10 +103,22 @@ impl Observer {
         // NOTE: Setting these values usually causes a quarter second animation to occur, which is
         // undesirable.
         //
-        // However, since we're setting them inside an observer, there already is a transaction
-        // ongoing, and as such we don't need to wrap this in a `CATransaction` ourselves.
+        // The observer is usually called inside the transaction of the root layer's change, but
+        // that transaction doesn't necessarily have actions disabled, and the initial values are
+        // observed outside of any transaction. So wrap the changes in a transaction of our own.
+        CATransaction::begin();
+        CATransaction::setDisableActions(true);
+        self.apply(layer, key_path, new);
+        CATransaction::commit();
+    }
 
+    fn apply(&self, layer: &CALayer, key_path: Option<&NSString>, new: &AnyObject) {
         if key_path == Some(ns_string!("contentsScale")) {
+            // The user decides the scale factor instead.
+            if self.ivars().scale_pinned.load(Ordering::Relaxed) {
+                return;
+            }
+
             let new = unsafe { &*(new as *const AnyObject as *const NSNumber) };
             let scale_factor = new.as_cgfloat();
 
@@ -126,6 +153,8 @@ pub struct CGImpl<D, W> {
     width: usize,
     /// The height of the underlying buffer.
     height: usize,
+    /// The last presented buffer, with its width and height.
+    presented: Option<(Arc<Buffer>, usize, usize)>,
     window_handle: W,
     _display: PhantomData<D>,
 }
@@ -262,6 +291,7 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for CGImpl<
             color_space: SendCGColorSpace(color_space),
             width,
             height,
+            presented: None,
             _display: PhantomData,
             window_handle: window_src,
         })
@@ -272,33 +302,114 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for CGImpl<
         &self.window_handle
     }
 
+    fn buffer_count(&self) -> u8 {
+        // Every buffer is new.
+        0
+    }
+
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
         self.width = width.get() as usize;
         self.height = height.get() as usize;
         Ok(())
     }
 
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        Some((
+            NonZeroU32::new(self.width as u32)?,
+            NonZeroU32::new(self.height as u32)?,
+        ))
+    }
+
+    fn set_content_scale(&mut self, scale: Option<f64>) -> Result<(), SoftBufferError> {
+        self.observer
+            .ivars()
+            .scale_pinned
+            .store(scale.is_some(), Ordering::Relaxed);
+
+        // Go back to the scale factor of the root layer, like the observer would have done.
+        let scale_factor = scale.unwrap_or_else(|| self.root_layer.contentsScale());
+
+        // Avoid the implicit animation, see `present`.
+        CATransaction::begin();
+        CATransaction::setDisableActions(true);
+        self.layer.setContentsScale(scale_factor);
+        CATransaction::commit();
+
+        Ok(())
+    }
+
+    fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+        out.clear();
+        match &self.presented {
+            Some((buffer, width, height)) if (*width, *height) == (self.width, self.height) => {
+                // SAFETY: Buffers are only presented once all pixels have been written.
+                out.extend(buffer.0.iter().map(|pixel| unsafe { pixel.assume_init() }));
+            }
+            // Nothing has been presented at the current size.
+            _ => out.resize(self.width * self.height, 0),
+        }
+        Ok(())
+    }
+
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SoftBufferError::SizeNotSet);
+        }
+
         Ok(BufferImpl {
-            buffer: vec![0; self.width * self.height],
+            buffer: vec![MaybeUninit::new(0); self.width * self.height],
             imp: self,
         })
     }
+
+    unsafe fn buffer_mut_uninit(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SoftBufferError::SizeNotSet);
+        }
+
+        let len = self.width * self.height;
+        let mut buffer = Vec::with_capacity(len);
+        // SAFETY: `MaybeUninit` doesn't need to be initialized.
+        unsafe { buffer.set_len(len) };
+        Ok(BufferImpl { buffer, imp: self })
+    }
 }
 
 pub struct BufferImpl<'a, D, W> {
     imp: &'a mut CGImpl<D, W>,
-    buffer: Vec<u32>,
+    buffer: Vec<MaybeUninit<u32>>,
 }
 
 impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        NonZeroU32::new(self.imp.width as u32).expect("buffer width is zero")
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        NonZeroU32::new(self.imp.height as u32).expect("buffer height is zero")
+    }
+
     #[inline]
     fn pixels(&self) -> &[u32] {
-        &self.buffer
+        // SAFETY: The buffer is zeroed, or the caller of `buffer_mut_uninit` has initialized it.
+        unsafe { slice::from_raw_parts(self.buffer.as_ptr().cast(), self.buffer.len()) }
     }
 
     #[inline]
     fn pixels_mut(&mut self) -> &mut [u32] {
+        // SAFETY: As above.
+        unsafe { slice::from_raw_parts_mut(self.buffer.as_mut_ptr().cast(), self.buffer.len()) }
+    }
+
+    #[inline]
+    fn pixels_uninit(&self) -> &[MaybeUninit<u32>] {
+        &self.buffer
+    }
+
+    #[inline]
+    fn pixels_uninit_mut(&mut self) -> &mut [MaybeUninit<u32>] {
         &mut self.buffer
     }
 
@@ -307,7 +418,8 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_,
     }
 
     fn present(self) -> Result<(), SoftBufferError> {
-        let data_provider = CGDataProvider::from_buffer(Arc::new(Buffer(self.buffer)));
+        let buffer = Arc::new(Buffer(self.buffer));
+        let data_provider = CGDataProvider::from_buffer(buffer.clone());
 
         let image = CGImage::new(
             self.imp.width,
@@ -333,12 +445,33 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_,
         unsafe { self.imp.layer.setContents(contents) };
 
         CATransaction::commit();
+
+        self.imp.presented = Some((buffer, self.imp.width, self.imp.height));
         Ok(())
     }
 
-    fn present_with_damage(self, _damage: &[Rect]) -> Result<(), SoftBufferError> {
+    fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError> {
+        if damage.is_empty() {
+            // Nothing changed, keep showing the current contents.
+            return Ok(());
+        }
         self.present()
     }
+
+    fn present_with_damage_reported(self, damage: &[Rect]) -> Result<Vec<Rect>, SoftBufferError> {
+        if damage.is_empty() {
+            // Nothing changed, keep showing the current contents.
+            return Ok(Vec::new());
+        }
+        let (width, height) = (self.width(), self.height());
+        self.present()?;
+        Ok(vec![Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }])
+    }
 }
 
 struct SendCGColorSpace(CGColorSpace);
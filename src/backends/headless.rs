@@ -0,0 +1,474 @@
+//! A backend that isn't attached to any window, and just keeps the presented buffer in memory.
+//!
+//! This is useful for testing rendering code without a windowing system.
+
+use crate::backend_interface::*;
+use crate::error::InitError;
+use crate::{util, NoWindowHandle, Rect, SoftBufferError};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::num::NonZeroU32;
+
+/// The context of the headless backend.
+///
+/// This never gets created from a display handle, headless surfaces are created with
+/// [`Surface::new_headless`](crate::Surface::new_headless) instead.
+#[derive(Clone)]
+pub struct HeadlessContext<D>(Infallible, PhantomData<D>);
+
+impl<D: HasDisplayHandle> ContextInterface<D> for HeadlessContext<D> {
+    fn new(display: D) -> Result<Self, InitError<D>> {
+        Err(InitError::Unsupported(display))
+    }
+
+    fn display(&self) -> &D {
+        match self.0 {}
+    }
+}
+
+pub struct HeadlessImpl<D, W> {
+    /// The buffer that we're drawing to.
+    buffer: Vec<u32>,
+
+    /// The contents of the buffer as of the last present, without any padding.
+    presented: Vec<u32>,
+
+    /// Buffer has been presented.
+    buffer_presented: bool,
+
+    /// The current width/height.
+    size: Option<(NonZeroU32, NonZeroU32)>,
+
+    /// The number of pixels in each row of `buffer`.
+    stride: usize,
+
+    /// Whether the rows of `buffer` are presented bottom to top.
+    vertical_flip: bool,
+
+    /// The underlying window handle.
+    window_handle: W,
+
+    /// The underlying display handle.
+    _display: PhantomData<D>,
+}
+
+impl<D: HasDisplayHandle> HeadlessImpl<D, NoWindowHandle> {
+    pub(crate) fn new_headless() -> Self {
+        Self {
+            buffer: Vec::new(),
+            presented: Vec::new(),
+            buffer_presented: false,
+            size: None,
+            stride: 0,
+            vertical_flip: false,
+            window_handle: NoWindowHandle(()),
+            _display: PhantomData,
+        }
+    }
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> HeadlessImpl<D, W> {
+    fn present_with_damage(&mut self, damage: &[Rect]) -> Result<(), SoftBufferError> {
+        let (width, height) = self.size.ok_or(SoftBufferError::SizeNotSet)?;
+        util::validate_damage(damage, width, height)?;
+
+        for rect in damage {
+            let x = rect.x as usize;
+            let rect_width = rect.width.get() as usize;
+            for y in rect.y as usize..(rect.y + rect.height.get()) as usize {
+                let src = y * self.stride + x;
+                let dst_y = if self.vertical_flip {
+                    height.get() as usize - 1 - y
+                } else {
+                    y
+                };
+                let dst = dst_y * width.get() as usize + x;
+                self.presented[dst..dst + rect_width]
+                    .copy_from_slice(&self.buffer[src..src + rect_width]);
+            }
+        }
+
+        self.buffer_presented = true;
+
+        Ok(())
+    }
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for HeadlessImpl<D, W> {
+    type Context = HeadlessContext<D>;
+    type Buffer<'a>
+        = BufferImpl<'a, D, W>
+    where
+        Self: 'a;
+
+    fn new(window: W, _context: &HeadlessContext<D>) -> Result<Self, InitError<W>> {
+        Err(InitError::Unsupported(window))
+    }
+
+    #[inline]
+    fn window(&self) -> &W {
+        &self.window_handle
+    }
+
+    fn supports_partial_present(&self) -> bool {
+        true
+    }
+
+    fn set_vertical_flip(&mut self, flip: bool) -> Result<(), SoftBufferError> {
+        self.vertical_flip = flip;
+        Ok(())
+    }
+
+    fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
+        self.resize_with_stride(width, height, width)
+    }
+
+    fn resize_with_stride(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+        stride: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        let stride = stride.get() as usize;
+        if self.size != Some((width, height)) || self.stride != stride {
+            let len = stride
+                .checked_mul(height.get() as usize)
+                .ok_or(SoftBufferError::SizeOutOfRange { width, height })?;
+
+            self.buffer_presented = false;
+            self.buffer.resize(len, 0);
+            self.presented.clear();
+            self.presented
+                .resize(width.get() as usize * height.get() as usize, 0);
+            self.size = Some((width, height));
+            self.stride = stride;
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        self.size
+    }
+
+    fn resize_preserving(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        // `presented` has no padding, unlike `buffer`.
+        let old = match self.size {
+            Some((old_width, _)) if self.buffer_presented && self.size != Some((width, height)) => {
+                Some((old_width, std::mem::take(&mut self.presented)))
+            }
+            _ => None,
+        };
+
+        self.resize(width, height)?;
+
+        if let Some((old_width, old)) = old {
+            util::copy_overlapping(
+                &old,
+                old_width.get() as usize,
+                &mut self.buffer,
+                width.get() as usize,
+            );
+            self.presented.copy_from_slice(&self.buffer);
+            if self.vertical_flip {
+                // Keep the contents in place on screen.
+                self.buffer.reverse();
+                self.buffer
+                    .chunks_exact_mut(width.get() as usize)
+                    .for_each(<[u32]>::reverse);
+            }
+            self.buffer_presented = true;
+        }
+
+        Ok(())
+    }
+
+    fn present_tile(&mut self, pixels: &[u32], tile: Rect) -> Result<(), SoftBufferError> {
+        let (width, height) = self.size.ok_or(SoftBufferError::SizeNotSet)?;
+        util::validate_damage(&[tile], width, height)?;
+
+        let tile_width = tile.width.get() as usize;
+        for (row, src) in pixels.chunks_exact(tile_width).enumerate() {
+            let dst = (tile.y as usize + row) * width.get() as usize + tile.x as usize;
+            self.presented[dst..dst + tile_width].copy_from_slice(src);
+        }
+
+        Ok(())
+    }
+
+    fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
+        if self.size.is_none() {
+            return Err(SoftBufferError::SizeNotSet);
+        }
+
+        Ok(BufferImpl { imp: self })
+    }
+
+    fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+        if self.size.is_none() {
+            return Err(SoftBufferError::SizeNotSet);
+        }
+
+        out.clear();
+        out.extend_from_slice(&self.presented);
+        Ok(())
+    }
+
+    fn fetch_rect(&mut self, rect: Rect) -> Result<Vec<u32>, SoftBufferError> {
+        let (width, _) = self.size.ok_or(SoftBufferError::SizeNotSet)?;
+
+        let (x, rect_width) = (rect.x as usize, rect.width.get() as usize);
+        Ok((rect.y as usize..(rect.y + rect.height.get()) as usize)
+            .flat_map(|y| {
+                let start = y * width.get() as usize + x;
+                &self.presented[start..start + rect_width]
+            })
+            .copied()
+            .collect())
+    }
+}
+
+pub struct BufferImpl<'a, D, W> {
+    imp: &'a mut HeadlessImpl<D, W>,
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        self.imp.size.unwrap().0
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        self.imp.size.unwrap().1
+    }
+
+    #[inline]
+    fn stride(&self) -> NonZeroU32 {
+        NonZeroU32::new(self.imp.stride as u32).unwrap()
+    }
+
+    #[inline]
+    fn pixels(&self) -> &[u32] {
+        &self.imp.buffer
+    }
+
+    #[inline]
+    fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.imp.buffer
+    }
+
+    fn age(&self) -> u8 {
+        if self.imp.buffer_presented {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn present(self) -> Result<(), SoftBufferError> {
+        let (width, height) = self.imp.size.unwrap();
+        self.imp.present_with_damage(&[Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }])
+    }
+
+    fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError> {
+        self.imp.present_with_damage(damage)
+    }
+
+    fn present_and_fetch(self) -> Result<Vec<u32>, PresentAndFetchError> {
+        let imp = self.imp;
+        BufferImpl { imp: &mut *imp }
+            .present()
+            .map_err(PresentAndFetchError::Present)?;
+        imp.fetch().map_err(PresentAndFetchError::Fetch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{NoDisplayHandle, NoWindowHandle, Rect, Surface};
+    use std::num::NonZeroU32;
+
+    fn surface(width: u32, height: u32) -> Surface<NoDisplayHandle, NoWindowHandle> {
+        Surface::new_headless(
+            NonZeroU32::new(width).unwrap(),
+            NonZeroU32::new(height).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width: NonZeroU32::new(width).unwrap(),
+            height: NonZeroU32::new(height).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_present_with_damage() {
+        let mut surface = surface(2, 2);
+        let mut buffer = surface.buffer_mut().unwrap();
+        buffer.copy_from_slice(&[1, 2, 3, 4]);
+        buffer.present_with_damage(&[rect(1, 0, 1, 2)]).unwrap();
+        assert_eq!(surface.fetch().unwrap(), [0, 2, 0, 4]);
+
+        let buffer = surface.buffer_mut().unwrap();
+        assert!(buffer.present_with_damage(&[rect(1, 1, 2, 1)]).is_err());
+    }
+
+    #[test]
+    fn test_stride() {
+        let mut surface = surface(2, 2);
+        let size = NonZeroU32::new(2).unwrap();
+        surface
+            .resize_with_stride(size, size, NonZeroU32::new(3).unwrap())
+            .unwrap();
+
+        let mut buffer = surface.buffer_mut().unwrap();
+        assert_eq!(buffer.stride().get(), 3);
+        buffer.copy_from_slice(&[1, 2, 9, 3, 4, 9]);
+        buffer.present_with_damage(&[rect(1, 0, 1, 2)]).unwrap();
+        // The padding isn't presented, and neither is the left column.
+        assert_eq!(surface.fetch().unwrap(), [0, 2, 0, 4]);
+
+        surface.buffer_mut().unwrap().present().unwrap();
+        assert_eq!(surface.fetch().unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_frame_presents_on_drop() {
+        let mut surface = surface(2, 2);
+        {
+            let mut frame = surface.begin_frame().unwrap();
+            frame.buffer_mut().fill(5);
+            frame.add_damage(rect(0, 1, 2, 1));
+        }
+
+        assert_eq!(surface.fetch().unwrap(), [0, 0, 5, 5]);
+    }
+
+    #[test]
+    fn test_present_tile() {
+        let mut surface = surface(3, 3);
+        surface
+            .present_tile(&[1, 2, 3, 4], rect(1, 1, 2, 2))
+            .unwrap();
+
+        assert_eq!(surface.fetch().unwrap(), [0, 0, 0, 0, 1, 2, 0, 3, 4]);
+        assert_eq!(surface.fetch_rect(rect(1, 2, 2, 1)).unwrap(), [3, 4]);
+        assert!(surface.present_tile(&[1, 2], rect(2, 0, 2, 1)).is_err());
+        // The tile isn't written into the buffer.
+        assert_eq!(*surface.buffer_mut().unwrap(), [0; 9]);
+    }
+
+    #[test]
+    fn test_resize_fill() {
+        let mut surface = surface(2, 2);
+        surface.set_resize_fill(Some(7));
+        let mut buffer = surface.buffer_mut().unwrap();
+        buffer.fill(1);
+        buffer.present().unwrap();
+
+        let size = NonZeroU32::new(3).unwrap();
+        surface.resize(size, size).unwrap();
+        let buffer = surface.buffer_mut().unwrap();
+        assert_eq!(buffer.age(), 0);
+        assert_eq!(*buffer, [7; 9]);
+        buffer.present().unwrap();
+
+        // Only the first buffer after growing is filled.
+        let mut buffer = surface.buffer_mut().unwrap();
+        buffer[0] = 1;
+        buffer.present().unwrap();
+        assert_eq!(surface.fetch().unwrap(), [1, 7, 7, 7, 7, 7, 7, 7, 7]);
+    }
+
+    #[test]
+    fn test_stale_rects() {
+        let mut surface = surface(2, 2);
+        let buffer = surface.buffer_mut().unwrap();
+        assert_eq!(buffer.stale_rects().map(|rects| rects.len()), None);
+        buffer.present().unwrap();
+
+        let buffer = surface.buffer_mut().unwrap();
+        buffer.present_with_damage(&[rect(0, 0, 1, 1)]).unwrap();
+
+        // There is only one buffer, so it always holds the last presented frame.
+        let buffer = surface.buffer_mut().unwrap();
+        assert_eq!(buffer.age(), 1);
+        assert_eq!(buffer.stale_rects().map(|rects| rects.len()), Some(0));
+    }
+
+    #[test]
+    fn test_present_pixels() {
+        let pixels = [1, 2, 3, 4, 5, 6];
+        let damage = [rect(1, 0, 1, 2), rect(0, 1, 2, 1)];
+
+        // Headless surfaces present the damaged regions directly.
+        let mut direct = surface(2, 3);
+        direct.present_pixels(&pixels, Some(&damage)).unwrap();
+        assert_eq!(*direct.buffer_mut().unwrap(), [0; 6]);
+
+        // Other backends fall back to copying the frame into a buffer and presenting that.
+        let mut fallback = surface(2, 3);
+        fallback.present_shared(&pixels, &damage).unwrap();
+        assert_eq!(*fallback.buffer_mut().unwrap(), pixels);
+
+        assert_eq!(direct.fetch().unwrap(), [0, 2, 3, 4, 0, 0]);
+        assert_eq!(fallback.fetch().unwrap(), direct.fetch().unwrap());
+    }
+
+    #[test]
+    fn test_resize_preserving_vertical_flip() {
+        let mut surface = surface(2, 2);
+        surface.set_vertical_flip(true).unwrap();
+
+        let mut buffer = surface.buffer_mut().unwrap();
+        buffer.copy_from_slice(&[1, 2, 3, 4]);
+        buffer.present().unwrap();
+        // The rows are presented bottom to top.
+        assert_eq!(surface.fetch().unwrap(), [3, 4, 1, 2]);
+
+        surface
+            .resize_preserving(NonZeroU32::new(2).unwrap(), NonZeroU32::new(3).unwrap())
+            .unwrap();
+        assert_eq!(surface.fetch().unwrap(), [3, 4, 1, 2, 0, 0]);
+
+        // Presenting the preserved buffer keeps the contents in place.
+        let buffer = surface.buffer_mut().unwrap();
+        assert_eq!(buffer.age(), 1);
+        buffer.present().unwrap();
+        assert_eq!(surface.fetch().unwrap(), [3, 4, 1, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_tracked_buffer() {
+        let mut surface = surface(2, 4);
+        let mut buffer = surface.buffer_mut().unwrap().with_damage_tracking();
+        buffer.row_mut(0).fill(1);
+        buffer.row_mut(1).fill(2);
+        buffer.row_mut(3).fill(3);
+
+        let damage: Vec<_> = buffer
+            .damage()
+            .iter()
+            .map(|rect| (rect.x, rect.y, rect.width.get(), rect.height.get()))
+            .collect();
+        assert_eq!(damage, [(0, 0, 2, 2), (0, 3, 2, 1)]);
+
+        buffer.present_tracked().unwrap();
+        assert_eq!(surface.fetch().unwrap(), [1, 1, 2, 2, 0, 0, 3, 3]);
+    }
+}
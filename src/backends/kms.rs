@@ -1,21 +1,31 @@
 //! Backend for DRM/KMS for raw rendering directly to the screen.
 //!
 //! This strategy uses dumb buffers for rendering.
+//!
+//! Buffers are flipped with atomic commits if the driver supports atomic modesetting, which
+//! enables `DRM_CLIENT_CAP_ATOMIC` on the device. Otherwise, legacy page flips are used.
 
 use drm::buffer::{Buffer, DrmFourcc};
+use drm::control::atomic::AtomicModeReq;
 use drm::control::dumbbuffer::{DumbBuffer, DumbMapping};
 use drm::control::{
-    connector, crtc, framebuffer, plane, ClipRect, Device as CtrlDevice, PageFlipFlags,
+    connector, crtc, framebuffer, plane, property, AtomicCommitFlags, ClipRect,
+    Device as CtrlDevice, Event, PageFlipFlags, PlaneType,
 };
-use drm::Device;
+use drm::{ClientCapability, Device, DriverCapability};
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use rustix::event::{poll, PollFd, PollFlags};
+use rustix::io::Errno;
+use rustix::time::{clock_gettime, ClockId};
 
 use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::num::NonZeroU32;
+use std::ops::{Bound, RangeBounds};
 use std::os::unix::io::{AsFd, BorrowedFd};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::backend_interface::*;
 use crate::error::{InitError, SoftBufferError, SwResultExt};
@@ -26,7 +36,7 @@ pub(crate) struct KmsDisplayImpl<D: ?Sized> {
     fd: BorrowedFd<'static>,
 
     /// Holds a reference to the display.
-    _display: D,
+    display: D,
 }
 
 impl<D: ?Sized> AsFd for KmsDisplayImpl<D> {
@@ -38,6 +48,18 @@ impl<D: ?Sized> AsFd for KmsDisplayImpl<D> {
 impl<D: ?Sized> Device for KmsDisplayImpl<D> {}
 impl<D: ?Sized> CtrlDevice for KmsDisplayImpl<D> {}
 
+/// The raw device file descriptor, for `drm` methods that need a `Sized` device.
+struct DeviceFd(BorrowedFd<'static>);
+
+impl AsFd for DeviceFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0
+    }
+}
+
+impl Device for DeviceFd {}
+impl CtrlDevice for DeviceFd {}
+
 impl<D: HasDisplayHandle + ?Sized> ContextInterface<D> for Arc<KmsDisplayImpl<D>> {
     fn new(display: D) -> Result<Self, InitError<D>>
     where
@@ -53,10 +75,11 @@ impl<D: HasDisplayHandle + ?Sized> ContextInterface<D> for Arc<KmsDisplayImpl<D>
         // SAFETY: Invariants guaranteed by the user.
         let fd = unsafe { BorrowedFd::borrow_raw(drm.fd) };
 
-        Ok(Arc::new(KmsDisplayImpl {
-            fd,
-            _display: display,
-        }))
+        Ok(Arc::new(KmsDisplayImpl { fd, display }))
+    }
+
+    fn display(&self) -> &D {
+        &self.display
     }
 }
 
@@ -72,13 +95,316 @@ pub(crate) struct KmsImpl<D: ?Sized, W: ?Sized> {
     /// The CRTC to render to.
     crtc: crtc::Info,
 
+    /// The plane to flip with atomic commits, if the driver supports them.
+    atomic: Option<AtomicPlane>,
+
     /// The dumb buffer we're using as a buffer.
     buffer: Option<Buffers>,
 
+    /// The largest framebuffer size supported by the device.
+    maximum_size: (NonZeroU32, NonZeroU32),
+
+    /// Whether we've requested a page flip that hasn't completed yet, as far as we know.
+    flip_pending: bool,
+
+    /// When the last page flip on our CRTC completed, on the `CLOCK_MONOTONIC` clock.
+    last_vblank: Option<Duration>,
+
+    /// The hardware cursor, once one has been set.
+    cursor: Option<Cursor>,
+
     /// Window handle that we are keeping around.
     window_handle: W,
 }
 
+impl<D: ?Sized, W: ?Sized> KmsImpl<D, W> {
+    /// Wait up to `timeout` milliseconds for the device to become readable.
+    ///
+    /// Returns `false` if it didn't, or if the wait was interrupted by a signal.
+    fn poll_device(&self, timeout: i32) -> Result<bool, SoftBufferError> {
+        let device = DeviceFd(self.display.fd);
+        match poll(&mut [PollFd::new(&device, PollFlags::IN)], timeout) {
+            Ok(ready) => Ok(ready > 0),
+            Err(Errno::INTR) => Ok(false),
+            Err(err) => Err(SoftBufferError::PlatformError(
+                Some("failed to poll DRM device".into()),
+                Some(Box::new(std::io::Error::from(err))),
+            )),
+        }
+    }
+
+    /// Read the pending events from the device, and return the last page flip on our CRTC.
+    fn receive_page_flip(&mut self) -> Result<Option<VblankInfo>, SoftBufferError> {
+        let events = DeviceFd(self.display.fd)
+            .receive_events()
+            .swbuf_err("failed to receive DRM events")?;
+
+        let mut flip = None;
+        for event in events {
+            if let Event::PageFlip(event) = event {
+                if event.crtc == self.crtc.handle() {
+                    self.flip_pending = false;
+                    self.last_vblank = Some(event.duration);
+                    flip = Some(VblankInfo {
+                        frame: event.frame,
+                        time: event.duration,
+                    });
+                }
+            }
+        }
+
+        Ok(flip)
+    }
+
+    /// Estimate when the next vblank after now happens, from the last page flip and the refresh
+    /// rate of the CRTC's mode.
+    fn next_vblank_hint(&self) -> Option<Instant> {
+        let last_vblank = self.last_vblank?.as_nanos();
+        let mode = self.crtc.mode()?;
+
+        // The refresh period is the number of pixels in a frame, including blanking, divided by
+        // the pixel clock in kHz.
+        let (_, _, htotal) = mode.hsync();
+        let (_, _, vtotal) = mode.vsync();
+        let period = (u128::from(htotal) * u128::from(vtotal) * 1_000_000)
+            .checked_div(u128::from(mode.clock()))
+            .filter(|&period| period > 0)?;
+
+        // Page flip events are timestamped on the monotonic clock, which `Instant` can't be
+        // created from, so measure how far away the next vblank is from now.
+        let (now_instant, now) = (Instant::now(), clock_gettime(ClockId::Monotonic));
+        let now = now.tv_sec as u128 * 1_000_000_000 + now.tv_nsec as u128;
+        let since = now.saturating_sub(last_vblank);
+        let next = last_vblank + (since / period + 1) * period;
+        Some(now_instant + Duration::from_nanos((next - now) as u64))
+    }
+
+    /// Show `pixels` on the cursor plane, or hide the cursor if `pixels` is empty.
+    fn set_cursor(&mut self, pixels: &[u32], hotspot: (u32, u32)) -> Result<(), SoftBufferError> {
+        if pixels.is_empty() {
+            if let Some(cursor) = &mut self.cursor {
+                cursor.visible = false;
+                cursor.update(&self.display, self.crtc.handle())?;
+            }
+            return Ok(());
+        }
+
+        if self.cursor.is_none() {
+            self.cursor = Some(Cursor::new(&self.display, self.crtc.handle())?);
+        }
+        let cursor = self.cursor.as_mut().unwrap();
+
+        let (width, height) = cursor.buffer.db.size();
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize,
+            "cursor image doesn't match the cursor size"
+        );
+
+        // The rows of the dumb buffer may be padded.
+        let pitch = cursor.buffer.db.pitch() as usize;
+        let mut mapping = self
+            .display
+            .map_dumb_buffer(&mut cursor.buffer.db)
+            .swbuf_err("failed to map cursor buffer")?;
+        for (row, src) in mapping
+            .as_mut()
+            .chunks_exact_mut(pitch)
+            .zip(pixels.chunks_exact(width as usize))
+        {
+            row[..src.len() * 4].copy_from_slice(bytemuck::cast_slice(src));
+        }
+        drop(mapping);
+
+        cursor.hotspot = hotspot;
+        cursor.visible = true;
+        cursor.update(&self.display, self.crtc.handle())
+    }
+
+    /// Move the hotspot of the cursor to `position` on the CRTC.
+    fn move_cursor(&mut self, position: (i32, i32)) -> Result<(), SoftBufferError> {
+        let Some(cursor) = &mut self.cursor else {
+            return Err(SoftBufferError::PlatformError(
+                Some("no cursor has been set".into()),
+                None,
+            ));
+        };
+
+        cursor.position = position;
+        if cursor.visible {
+            cursor.update(&self.display, self.crtc.handle())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A completed page flip, as reported by [`SurfaceExtKms::read_vblank_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VblankInfo {
+    /// The vblank sequence number of the frame the buffer was shown in.
+    pub frame: u32,
+
+    /// When the vblank happened, on the `CLOCK_MONOTONIC` clock.
+    pub time: Duration,
+}
+
+/// Extension methods for the DRM/KMS platform on [`Surface`](crate::Surface).
+///
+/// These allow driving rendering from an existing event loop: wait for [`SurfaceExtKms::vblank_fd`]
+/// to become readable with `poll` or `epoll`, call [`SurfaceExtKms::read_vblank_event`], and draw
+/// the next frame once the previous one has been flipped.
+pub trait SurfaceExtKms {
+    /// The file descriptor of the DRM device, which becomes readable when a page flip completes.
+    ///
+    /// Returns `None` if the surface doesn't use DRM/KMS.
+    fn vblank_fd(&self) -> Option<BorrowedFd<'_>>;
+
+    /// Read the pending events from the DRM device without blocking, and return the page flip
+    /// of the last present if it has completed.
+    ///
+    /// Like [`Surface::wait_for_vblank`](crate::Surface::wait_for_vblank), this discards events
+    /// meant for other users of the device. Returns [`SoftBufferError::Unimplemented`] if the
+    /// surface doesn't use DRM/KMS.
+    fn read_vblank_event(&mut self) -> Result<Option<VblankInfo>, SoftBufferError>;
+
+    /// Whether the last present is still waiting for a vblank to be shown.
+    ///
+    /// This is cleared by reading the page flip with [`SurfaceExtKms::read_vblank_event`] or
+    /// [`Surface::wait_for_vblank`](crate::Surface::wait_for_vblank). Returns `false` if the
+    /// surface doesn't use DRM/KMS.
+    fn vblank_pending(&self) -> bool;
+
+    /// An estimate of when the next vblank happens, to schedule rendering so that the frame is
+    /// ready just before it.
+    ///
+    /// This is extrapolated from the last page flip that was read, using the refresh rate of the
+    /// CRTC's mode. Returns `None` if the surface doesn't use DRM/KMS, if no page flip has been
+    /// read yet, or if the CRTC has no mode.
+    fn next_vblank_hint(&self) -> Option<Instant>;
+
+    /// The size of the images accepted by [`SurfaceExtKms::set_cursor`].
+    ///
+    /// This is reported by the driver, and is usually 64x64. Returns `None` if the surface doesn't
+    /// use DRM/KMS.
+    fn cursor_size(&self) -> Option<(u32, u32)>;
+
+    /// Show a hardware cursor on the cursor plane of the surface's CRTC.
+    ///
+    /// `pixels` is an ARGB image of [`SurfaceExtKms::cursor_size`], with the alpha channel in the
+    /// upper 8 bits, and `hotspot` is the point of the image that follows
+    /// [`SurfaceExtKms::move_cursor`]. An empty `pixels` hides the cursor again.
+    ///
+    /// The cursor plane is updated independently of the presented buffers, so moving the cursor
+    /// doesn't require presenting anything. Returns [`SoftBufferError::Unimplemented`] if the
+    /// surface doesn't use DRM/KMS, or if the device doesn't have a cursor plane for the CRTC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` is not empty and its length doesn't match the cursor size.
+    fn set_cursor(&mut self, pixels: &[u32], hotspot: (u32, u32)) -> Result<(), SoftBufferError>;
+
+    /// Move the hotspot of the cursor to `(x, y)`, in pixels from the top left corner of the CRTC.
+    ///
+    /// The position is kept while the cursor is hidden. Returns an error if no cursor has been
+    /// set with [`SurfaceExtKms::set_cursor`].
+    fn move_cursor(&mut self, x: i32, y: i32) -> Result<(), SoftBufferError>;
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceExtKms for crate::Surface<D, W> {
+    fn vblank_fd(&self) -> Option<BorrowedFd<'_>> {
+        match &*self.surface_impl {
+            crate::SurfaceDispatch::Kms(imp) => Some(imp.display.as_fd()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    fn read_vblank_event(&mut self) -> Result<Option<VblankInfo>, SoftBufferError> {
+        match &mut *self.surface_impl {
+            crate::SurfaceDispatch::Kms(imp) => {
+                if imp.poll_device(0)? {
+                    imp.receive_page_flip()
+                } else {
+                    Ok(None)
+                }
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(SoftBufferError::Unimplemented),
+        }
+    }
+
+    fn vblank_pending(&self) -> bool {
+        match &*self.surface_impl {
+            crate::SurfaceDispatch::Kms(imp) => imp.flip_pending,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    fn next_vblank_hint(&self) -> Option<Instant> {
+        match &*self.surface_impl {
+            crate::SurfaceDispatch::Kms(imp) => imp.next_vblank_hint(),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    fn cursor_size(&self) -> Option<(u32, u32)> {
+        match &*self.surface_impl {
+            crate::SurfaceDispatch::Kms(imp) => Some(cursor_size(&imp.display)),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    fn set_cursor(&mut self, pixels: &[u32], hotspot: (u32, u32)) -> Result<(), SoftBufferError> {
+        match &mut *self.surface_impl {
+            crate::SurfaceDispatch::Kms(imp) => imp.set_cursor(pixels, hotspot),
+            #[allow(unreachable_patterns)]
+            _ => Err(SoftBufferError::Unimplemented),
+        }
+    }
+
+    fn move_cursor(&mut self, x: i32, y: i32) -> Result<(), SoftBufferError> {
+        match &mut *self.surface_impl {
+            crate::SurfaceDispatch::Kms(imp) => imp.move_cursor((x, y)),
+            #[allow(unreachable_patterns)]
+            _ => Err(SoftBufferError::Unimplemented),
+        }
+    }
+}
+
+/// A plane that is updated with atomic commits instead of legacy page flips.
+#[derive(Debug, Clone, Copy)]
+struct AtomicPlane {
+    /// The plane to put the framebuffer on.
+    plane: plane::Handle,
+
+    /// The `FB_ID` property of the plane.
+    fb_id: property::Handle,
+}
+
+/// A hardware cursor on a cursor plane.
+#[derive(Debug)]
+struct Cursor {
+    /// The cursor plane.
+    plane: plane::Handle,
+
+    /// The ARGB buffer holding the cursor image.
+    buffer: SharedBuffer,
+
+    /// The point of the image that is placed at `position`.
+    hotspot: (u32, u32),
+
+    /// The position of the hotspot on the CRTC.
+    position: (i32, i32),
+
+    /// Whether the cursor plane is enabled.
+    visible: bool,
+}
+
 #[derive(Debug)]
 struct Buffers {
     /// The involved set of buffers.
@@ -99,12 +425,18 @@ pub(crate) struct BufferImpl<'a, D: ?Sized, W: ?Sized> {
     /// The CRTC handle.
     crtc_handle: crtc::Handle,
 
+    /// The plane to flip with atomic commits, if the driver supports them.
+    atomic: Option<AtomicPlane>,
+
     /// This is used to change the front buffer.
     first_is_front: &'a mut bool,
 
     /// The current size.
     size: (NonZeroU32, NonZeroU32),
 
+    /// The number of pixels in each row of the mapping, including padding.
+    stride: NonZeroU32,
+
     /// The display implementation.
     display: &'a KmsDisplayImpl<D>,
 
@@ -114,6 +446,9 @@ pub(crate) struct BufferImpl<'a, D: ?Sized, W: ?Sized> {
     /// Age of the back buffer.
     back_age: &'a mut u8,
 
+    /// Set when a page flip is requested.
+    flip_pending: &'a mut bool,
+
     /// Window reference.
     _window: PhantomData<&'a mut W>,
 }
@@ -197,11 +532,27 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
             .map(|info| info.handle())
             .collect::<Vec<_>>();
 
+        let maximum_size = (
+            max_dimension(handles.supported_fb_width()),
+            max_dimension(handles.supported_fb_height()),
+        );
+
+        // Flip with atomic commits if the driver supports them. This only replaces the
+        // framebuffer, so the plane has to be set up on the CRTC already.
+        let atomic = plane_info
+            .crtc()
+            .and_then(|_| atomic_plane(display, plane_handle));
+
         Ok(Self {
             crtc,
+            atomic,
             connectors,
             display: display.clone(),
             buffer: None,
+            maximum_size,
+            flip_pending: false,
+            last_vblank: None,
+            cursor: None,
             window_handle: window,
         })
     }
@@ -211,6 +562,20 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
         &self.window_handle
     }
 
+    fn buffer_count(&self) -> u8 {
+        2
+    }
+
+    fn supports_partial_present(&self) -> bool {
+        // The whole framebuffer is flipped on every present. The damage is only forwarded to the
+        // driver with `DIRTYFB` as a hint, which most drivers ignore.
+        false
+    }
+
+    fn uses_shared_memory(&self) -> bool {
+        true
+    }
+
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
         // Don't resize if we don't have to.
         if let Some(buffer) = &self.buffer {
@@ -232,18 +597,61 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
         Ok(())
     }
 
-    /*
-    fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
-        // TODO: Implement this!
+    fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+        self.maximum_size
+    }
+
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        self.buffer.as_ref().map(|buffer| buffer.size())
+    }
+
+    fn wait_for_vblank(&mut self) -> Result<(), SoftBufferError> {
+        while self.flip_pending {
+            // Wait for the device to become readable, in case the file descriptor is non-blocking.
+            if self.poll_device(-1)? {
+                self.receive_page_flip()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+        let set = self.buffer.as_mut().ok_or(SoftBufferError::SizeNotSet)?;
+        let (width, height) = set.size();
+        let (width, height) = (width.get() as usize, height.get() as usize);
+
+        // The buffer that is drawn to next is called the front buffer, so the one on screen is
+        // the other one.
+        let [first_buffer, second_buffer] = &mut set.buffers;
+        let shown = if set.first_is_front {
+            second_buffer
+        } else {
+            first_buffer
+        };
+
+        out.clear();
+        if shown.age == 0 {
+            // Nothing has been presented since the last resize.
+            out.resize(width * height, 0);
+            return Ok(());
+        }
+
+        let pitch = shown.db.pitch() as usize;
+        let mapping = self
+            .display
+            .map_dumb_buffer(&mut shown.db)
+            .swbuf_err("failed to map dumb buffer")?;
+        for row in mapping.as_ref().chunks(pitch).take(height) {
+            out.extend_from_slice(bytemuck::cast_slice(&row[..width * 4]));
+        }
+
+        Ok(())
     }
-    */
 
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
         // Map the dumb buffer.
-        let set = self
-            .buffer
-            .as_mut()
-            .expect("Must set size of surface before calling `buffer_mut()`");
+        let set = self.buffer.as_mut().ok_or(SoftBufferError::SizeNotSet)?;
 
         let size = set.size();
 
@@ -255,6 +663,8 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
         };
 
         let front_fb = front_buffer.fb;
+        // The driver may pad the rows.
+        let stride = NonZeroU32::new(front_buffer.db.pitch() / 4).unwrap_or(size.0);
         let front_age = &mut front_buffer.age;
         let back_age = &mut back_buffer.age;
 
@@ -266,12 +676,15 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
         Ok(BufferImpl {
             mapping,
             size,
+            stride,
             first_is_front: &mut set.first_is_front,
             front_fb,
             crtc_handle: self.crtc.handle(),
+            atomic: self.atomic,
             display: &self.display,
             front_age,
             back_age,
+            flip_pending: &mut self.flip_pending,
             _window: PhantomData,
         })
     }
@@ -279,6 +692,14 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
 
 impl<D: ?Sized, W: ?Sized> Drop for KmsImpl<D, W> {
     fn drop(&mut self) {
+        // Take down the cursor, if we've put one up.
+        if let Some(mut cursor) = self.cursor.take() {
+            cursor.visible = false;
+            cursor.update(&self.display, self.crtc.handle()).ok();
+            self.display.destroy_framebuffer(cursor.buffer.fb).ok();
+            self.display.destroy_dumb_buffer(cursor.buffer.db).ok();
+        }
+
         // Map the CRTC to the information that was there before.
         self.display
             .set_crtc(
@@ -293,6 +714,21 @@ impl<D: ?Sized, W: ?Sized> Drop for KmsImpl<D, W> {
 }
 
 impl<D: ?Sized, W: ?Sized> BufferInterface for BufferImpl<'_, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        self.size.0
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        self.size.1
+    }
+
+    #[inline]
+    fn stride(&self) -> NonZeroU32 {
+        self.stride
+    }
+
     #[inline]
     fn pixels(&self) -> &[u32] {
         bytemuck::cast_slice(self.mapping.as_ref())
@@ -335,22 +771,41 @@ impl<D: ?Sized, W: ?Sized> BufferInterface for BufferImpl<'_, D, W> {
         // TODO: It would be nice to not have to heap-allocate the above rectangles if we know that
         // this is going to fail. Low hanging fruit PR: add a flag that's set to false if this
         // returns `ENOSYS` and check that before allocating the above and running this.
-        match self.display.dirty_framebuffer(self.front_fb, &rectangles) {
-            Ok(()) => {}
-            Err(e) if e.raw_os_error() == Some(rustix::io::Errno::NOSYS.raw_os_error()) => {}
-            Err(e) => {
-                return Err(SoftBufferError::PlatformError(
-                    Some("failed to dirty framebuffer".into()),
-                    Some(e.into()),
-                ));
+        //
+        // Without any rectangles, the whole framebuffer would be marked as dirty.
+        if !rectangles.is_empty() {
+            match self.display.dirty_framebuffer(self.front_fb, &rectangles) {
+                Ok(()) => {}
+                Err(e) if e.raw_os_error() == Some(rustix::io::Errno::NOSYS.raw_os_error()) => {}
+                Err(e) => {
+                    return Err(SoftBufferError::PlatformError(
+                        Some("failed to dirty framebuffer".into()),
+                        Some(e.into()),
+                    ));
+                }
             }
         }
 
         // Swap the buffers.
-        // TODO: Use atomic commits here!
-        self.display
-            .page_flip(self.crtc_handle, self.front_fb, PageFlipFlags::EVENT, None)
-            .swbuf_err("failed to page flip")?;
+        if let Some(atomic) = self.atomic {
+            let mut request = AtomicModeReq::new();
+            request.add_property(
+                atomic.plane,
+                atomic.fb_id,
+                property::Value::Framebuffer(Some(self.front_fb)),
+            );
+            self.display
+                .atomic_commit(
+                    AtomicCommitFlags::PAGE_FLIP_EVENT | AtomicCommitFlags::NONBLOCK,
+                    request,
+                )
+                .swbuf_err("failed to commit atomic page flip")?;
+        } else {
+            self.display
+                .page_flip(self.crtc_handle, self.front_fb, PageFlipFlags::EVENT, None)
+                .swbuf_err("failed to page flip")?;
+        }
+        *self.flip_pending = true;
 
         // Flip the front and back buffers.
         *self.first_is_front = !*self.first_is_front;
@@ -403,9 +858,151 @@ impl SharedBuffer {
     }
 }
 
+impl Cursor {
+    /// Find the cursor plane of `crtc`, and create a buffer for the cursor image.
+    fn new<D: ?Sized>(
+        display: &KmsDisplayImpl<D>,
+        crtc: crtc::Handle,
+    ) -> Result<Self, SoftBufferError> {
+        // Cursor planes are only listed with universal planes enabled.
+        display
+            .set_client_capability(ClientCapability::UniversalPlanes, true)
+            .swbuf_err("failed to enable universal planes")?;
+
+        let handles = display
+            .resource_handles()
+            .swbuf_err("failed to get resource handles")?;
+        let plane = display
+            .plane_handles()
+            .swbuf_err("failed to get plane handles")?
+            .into_iter()
+            .find(|&plane| {
+                display
+                    .get_plane(plane)
+                    .is_ok_and(|info| handles.filter_crtcs(info.possible_crtcs()).contains(&crtc))
+                    && plane_type(display, plane) == Some(PlaneType::Cursor)
+            })
+            .ok_or(SoftBufferError::Unimplemented)?;
+
+        let (width, height) = cursor_size(display);
+        let db = display
+            .create_dumb_buffer((width, height), DrmFourcc::Argb8888, 32)
+            .swbuf_err("failed to create cursor buffer")?;
+        let fb = display
+            .add_framebuffer(&db, 32, 32)
+            .swbuf_err("failed to add cursor framebuffer")?;
+
+        Ok(Self {
+            plane,
+            buffer: SharedBuffer { fb, db, age: 0 },
+            hotspot: (0, 0),
+            position: (0, 0),
+            visible: false,
+        })
+    }
+
+    /// Put the cursor plane in the current state of the cursor.
+    fn update<D: ?Sized>(
+        &self,
+        display: &KmsDisplayImpl<D>,
+        crtc: crtc::Handle,
+    ) -> Result<(), SoftBufferError> {
+        if !self.visible {
+            return display
+                .set_plane(self.plane, crtc, None, 0, (0, 0, 0, 0), (0, 0, 0, 0))
+                .swbuf_err("failed to disable cursor plane");
+        }
+
+        let (width, height) = self.buffer.db.size();
+        let x = self.position.0.saturating_sub(self.hotspot.0 as i32);
+        let y = self.position.1.saturating_sub(self.hotspot.1 as i32);
+
+        // The source rectangle is in 16.16 fixed point.
+        display
+            .set_plane(
+                self.plane,
+                crtc,
+                Some(self.buffer.fb),
+                0,
+                (x, y, width, height),
+                (0, 0, width << 16, height << 16),
+            )
+            .swbuf_err("failed to update cursor plane")
+    }
+}
+
 impl Buffers {
     /// Get the size of this buffer.
     pub(crate) fn size(&self) -> (NonZeroU32, NonZeroU32) {
         self.buffers[0].size()
     }
 }
+
+/// Enable atomic modesetting and look up what is needed to flip `plane` with it.
+///
+/// Returns `None` if the driver doesn't support atomic modesetting.
+fn atomic_plane<D: ?Sized>(
+    display: &KmsDisplayImpl<D>,
+    plane: plane::Handle,
+) -> Option<AtomicPlane> {
+    if let Err(err) = display.set_client_capability(ClientCapability::Atomic, true) {
+        tracing::debug!("atomic modesetting is not available, using legacy page flips: {err}");
+        return None;
+    }
+
+    let properties = display.get_properties(plane).ok()?;
+    let (handles, _) = properties.as_props_and_values();
+    let fb_id = handles.iter().copied().find(|&handle| {
+        display
+            .get_property(handle)
+            .is_ok_and(|info| info.name().to_bytes() == b"FB_ID")
+    })?;
+
+    Some(AtomicPlane { plane, fb_id })
+}
+
+/// The size of cursor images supported by the device.
+fn cursor_size<D: ?Sized>(display: &KmsDisplayImpl<D>) -> (u32, u32) {
+    // Drivers that don't report this use the historical default.
+    let dimension = |cap| {
+        display
+            .get_driver_capability(cap)
+            .ok()
+            .and_then(|size| u32::try_from(size).ok())
+            .filter(|&size| size != 0)
+            .unwrap_or(64)
+    };
+
+    (
+        dimension(DriverCapability::CursorWidth),
+        dimension(DriverCapability::CursorHeight),
+    )
+}
+
+/// The type of `plane`, as reported by its `type` property.
+fn plane_type<D: ?Sized>(display: &KmsDisplayImpl<D>, plane: plane::Handle) -> Option<PlaneType> {
+    let properties = display.get_properties(plane).ok()?;
+    let (handles, values) = properties.as_props_and_values();
+    let value = handles.iter().zip(values).find_map(|(&handle, &value)| {
+        display
+            .get_property(handle)
+            .is_ok_and(|info| info.name().to_bytes() == b"type")
+            .then_some(value)
+    })?;
+
+    [PlaneType::Overlay, PlaneType::Primary, PlaneType::Cursor]
+        .into_iter()
+        .find(|&ty| ty as u64 == value)
+}
+
+/// The largest value in a range of framebuffer dimensions, as reported by the device.
+fn max_dimension(range: impl RangeBounds<u32>) -> NonZeroU32 {
+    let max = match range.end_bound() {
+        Bound::Included(&max) => max,
+        Bound::Excluded(&max) => max.saturating_sub(1),
+        Bound::Unbounded => u32::MAX,
+    };
+
+    // Some drivers don't report a limit.
+    NonZeroU32::new(max).unwrap_or(NonZeroU32::MAX)
+}
@@ -5,6 +5,8 @@ use raw_window_handle::HasDisplayHandle;
 pub(crate) mod android;
 #[cfg(target_vendor = "apple")]
 pub(crate) mod cg;
+#[cfg(feature = "headless")]
+pub(crate) mod headless;
 #[cfg(kms_platform)]
 pub(crate) mod kms;
 #[cfg(target_os = "redox")]
@@ -22,4 +24,8 @@ impl<D: HasDisplayHandle> ContextInterface<D> for D {
     fn new(display: D) -> Result<Self, InitError<D>> {
         Ok(display)
     }
+
+    fn display(&self) -> &D {
+        self
+    }
 }
@@ -93,7 +93,7 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> OrbitalImpl<D, W> {
         (window_width, window_height)
     }
 
-    fn set_buffer(&self, buffer: &[u32], width_u32: u32, height_u32: u32) {
+    fn set_buffer(&self, buffer: &[u32], width_u32: u32, damage: &[Rect]) {
         // Read the current width and size
         let (window_width, window_height) = self.window_size();
 
@@ -106,16 +106,23 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> OrbitalImpl<D, W> {
             // Window buffer is u32 color data in 0xAABBGGRR format
             let window_data = unsafe { window_map.data_mut() };
 
-            // Copy each line, cropping to fit
+            // Copy each line of the damaged regions, cropping to fit
             let width = width_u32 as usize;
-            let height = height_u32 as usize;
-            let min_width = cmp::min(width, window_width);
-            let min_height = cmp::min(height, window_height);
-            for y in 0..min_height {
-                let offset_buffer = y * width;
-                let offset_data = y * window_width;
-                window_data[offset_data..offset_data + min_width]
-                    .copy_from_slice(&buffer[offset_buffer..offset_buffer + min_width]);
+            for rect in damage {
+                let x = rect.x as usize;
+                let y = rect.y as usize;
+                let max_x = cmp::min(x + rect.width.get() as usize, window_width);
+                let max_y = cmp::min(y + rect.height.get() as usize, window_height);
+                if x >= max_x {
+                    continue;
+                }
+
+                for y in y..max_y {
+                    let offset_buffer = y * width;
+                    let offset_data = y * window_width;
+                    window_data[offset_data + x..offset_data + max_x]
+                        .copy_from_slice(&buffer[offset_buffer + x..offset_buffer + max_x]);
+                }
             }
 
             // Window buffer map is dropped here
@@ -165,7 +172,15 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Orbital
         Ok(())
     }
 
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        Some((NonZeroU32::new(self.width)?, NonZeroU32::new(self.height)?))
+    }
+
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SoftBufferError::SizeNotSet);
+        }
+
         let (window_width, window_height) = self.window_size();
         let pixels = if self.width as usize == window_width && self.height as usize == window_height
         {
@@ -191,6 +206,16 @@ pub struct BufferImpl<'a, D, W> {
 }
 
 impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        NonZeroU32::new(self.imp.width).unwrap()
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        NonZeroU32::new(self.imp.height).unwrap()
+    }
+
     #[inline]
     fn pixels(&self) -> &[u32] {
         match &self.pixels {
@@ -215,22 +240,48 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_,
     }
 
     fn present(self) -> Result<(), SoftBufferError> {
+        let (width, height) = (self.width(), self.height());
+        self.present_with_damage(&[Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }])
+    }
+
+    fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError> {
         match self.pixels {
             Pixels::Mapping(mapping) => {
+                // The buffer is the window data, so it's all there already.
                 drop(mapping);
                 syscall::fsync(self.imp.window_fd()).expect("failed to sync orbital window");
                 self.imp.presented = true;
             }
             Pixels::Buffer(buffer) => {
-                self.imp
-                    .set_buffer(&buffer, self.imp.width, self.imp.height);
+                if !damage.is_empty() {
+                    self.imp.set_buffer(&buffer, self.imp.width, damage);
+                }
             }
         }
 
         Ok(())
     }
 
-    fn present_with_damage(self, _damage: &[Rect]) -> Result<(), SoftBufferError> {
-        self.present()
+    fn present_with_damage_reported(self, damage: &[Rect]) -> Result<Vec<Rect>, SoftBufferError> {
+        // The mapped window data is synced as a whole.
+        let reported = match self.pixels {
+            Pixels::Mapping(_) => {
+                let (width, height) = (self.width(), self.height());
+                vec![Rect {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                }]
+            }
+            Pixels::Buffer(_) => damage.to_vec(),
+        };
+        self.present_with_damage(damage)?;
+        Ok(reported)
     }
 }
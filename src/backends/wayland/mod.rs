@@ -1,18 +1,24 @@
 use crate::{
     backend_interface::*,
     error::{InitError, SwResultExt},
-    util, Rect, SoftBufferError,
+    util, PresentStats, Rect, SoftBufferError,
 };
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use rustix::event::{poll, PollFd, PollFlags};
+use rustix::io::Errno;
 use std::{
+    io,
     num::{NonZeroI32, NonZeroU32},
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use wayland_client::{
-    backend::{Backend, ObjectId},
+    backend::{Backend, ObjectId, WaylandError},
     globals::{registry_queue_init, GlobalListContents},
-    protocol::{wl_registry, wl_shm, wl_surface},
-    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+    protocol::{
+        wl_compositor, wl_region, wl_registry, wl_shm, wl_subcompositor, wl_subsurface, wl_surface,
+    },
+    Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle, WEnum,
 };
 
 mod buffer;
@@ -25,12 +31,17 @@ pub struct WaylandDisplayImpl<D: ?Sized> {
     event_queue: Mutex<EventQueue<State>>,
     qh: QueueHandle<State>,
     shm: wl_shm::WlShm,
+    compositor: Option<wl_compositor::WlCompositor>,
+    subcompositor: Option<wl_subcompositor::WlSubcompositor>,
+
+    /// The formats advertised by `wl_shm`, which are received once after binding it.
+    shm_formats: Vec<wl_shm::Format>,
 
     /// The object that owns the display handle.
     ///
     /// This has to be dropped *after* the `conn` field, because the `conn` field implicitly borrows
     /// this.
-    _display: D,
+    display: D,
 }
 
 impl<D: HasDisplayHandle + ?Sized> WaylandDisplayImpl<D> {
@@ -51,20 +62,40 @@ impl<D: HasDisplayHandle + ?Sized> ContextInterface<D> for Arc<WaylandDisplayImp
 
         let backend = unsafe { Backend::from_foreign_display(w.display.as_ptr().cast()) };
         let conn = Connection::from_backend(backend);
-        let (globals, event_queue) =
+        let (globals, mut event_queue) =
             registry_queue_init(&conn).swbuf_err("Failed to make round trip to server")?;
         let qh = event_queue.handle();
+        let shm_formats = Arc::new(Mutex::new(Vec::new()));
         let shm: wl_shm::WlShm = globals
-            .bind(&qh, 1..=1, ())
+            .bind(&qh, 1..=1, shm_formats.clone())
             .swbuf_err("Failed to instantiate Wayland Shm")?;
+        // Only needed to create regions and subsurfaces, so it's fine if these are missing.
+        // Version 4 gives subsurfaces `set_buffer_scale` and `damage_buffer`, regions only need
+        // version 1.
+        let compositor = globals.bind(&qh, 1..=4, ()).ok();
+        let subcompositor = globals.bind(&qh, 1..=1, ()).ok();
+
+        // Receive the formats that `wl_shm` sends right after it is bound.
+        event_queue
+            .roundtrip(&mut State)
+            .swbuf_err("Failed to make round trip to server")?;
+        let shm_formats = std::mem::take(&mut *shm_formats.lock().unwrap());
+
         Ok(Arc::new(WaylandDisplayImpl {
             conn: Some(conn),
             event_queue: Mutex::new(event_queue),
             qh,
             shm,
-            _display: display,
+            compositor,
+            subcompositor,
+            shm_formats,
+            display,
         }))
     }
+
+    fn display(&self) -> &D {
+        &self.display
+    }
 }
 
 impl<D: ?Sized> Drop for WaylandDisplayImpl<D> {
@@ -77,8 +108,31 @@ impl<D: ?Sized> Drop for WaylandDisplayImpl<D> {
 pub struct WaylandImpl<D: ?Sized, W: ?Sized> {
     display: Arc<WaylandDisplayImpl<D>>,
     surface: Option<wl_surface::WlSurface>,
-    buffers: Option<(WaylandBuffer, WaylandBuffer)>,
+
+    /// If `surface` was created by us as a child of the window's surface, its subsurface role and
+    /// position.
+    subsurface: Option<(wl_subsurface::WlSubsurface, (i32, i32))>,
+
+    /// The front buffer that was presented last, and the back buffer that is drawn to next.
+    ///
+    /// There is no front buffer with `single_buffer`, since the same buffer is presented every
+    /// time.
+    buffers: Option<(Option<WaylandBuffer>, WaylandBuffer)>,
+
+    /// Whether to present the back buffer without swapping buffers.
+    single_buffer: bool,
     size: Option<(NonZeroI32, NonZeroI32)>,
+    scale: i32,
+
+    /// The whole buffer needs to be damaged on the next commit, because the scale changed.
+    damage_all: bool,
+
+    /// How long `buffer_mut` may wait for the back buffer to be released.
+    present_timeout: Option<Duration>,
+
+    /// The measurements of the last `buffer_mut`, if they are turned on. `None` inside means that
+    /// nothing was measured yet.
+    present_stats: Option<Option<PresentStats>>,
 
     /// The pointer to the window object.
     ///
@@ -88,11 +142,68 @@ pub struct WaylandImpl<D: ?Sized, W: ?Sized> {
 }
 
 impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> WaylandImpl<D, W> {
+    fn with_surface(
+        window: W,
+        display: &Arc<WaylandDisplayImpl<D>>,
+        surface: wl_surface::WlSurface,
+        subsurface: Option<(wl_subsurface::WlSubsurface, (i32, i32))>,
+    ) -> Self {
+        Self {
+            display: display.clone(),
+            surface: Some(surface),
+            subsurface,
+            buffers: Default::default(),
+            single_buffer: false,
+            size: None,
+            scale: 1,
+            damage_all: false,
+            present_timeout: None,
+            present_stats: None,
+            window_handle: window,
+        }
+    }
+
+    /// Create a surface that is shown as a subsurface of the window's surface, at `position`
+    /// relative to it.
+    fn new_subsurface(
+        window: W,
+        display: &Arc<WaylandDisplayImpl<D>>,
+        position: (i32, i32),
+    ) -> Result<Self, InitError<W>> {
+        let parent = match surface_proxy(&window, display) {
+            Ok(surface) => surface,
+            Err(InitError::Unsupported(())) => return Err(InitError::Unsupported(window)),
+            Err(InitError::Failure(err)) => return Err(InitError::Failure(err)),
+        };
+        let (surface, subsurface) = create_subsurface(display, &parent, position)?;
+        Ok(Self::with_surface(
+            window,
+            display,
+            surface,
+            Some((subsurface, position)),
+        ))
+    }
+
     fn surface(&self) -> &wl_surface::WlSurface {
         self.surface.as_ref().unwrap()
     }
 
+    fn set_subsurface_position(&mut self, x: i32, y: i32) -> Result<(), SoftBufferError> {
+        let (subsurface, position) = self
+            .subsurface
+            .as_mut()
+            .ok_or(SoftBufferError::Unimplemented)?;
+        subsurface.set_position(x, y);
+        *position = (x, y);
+        Ok(())
+    }
+
     fn present_with_damage(&mut self, damage: &[Rect]) -> Result<(), SoftBufferError> {
+        // Only surfaces that we created are tracked, foreign proxies always appear to be alive.
+        if !self.surface().is_alive() {
+            return Err(SoftBufferError::WindowClosed);
+        }
+
         let _ = self
             .display
             .event_queue
@@ -101,20 +212,35 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> WaylandImpl<D, W> {
             .dispatch_pending(&mut State);
 
         if let Some((front, back)) = &mut self.buffers {
-            // Swap front and back buffer
-            std::mem::swap(front, back);
-
-            front.age = 1;
-            if back.age != 0 {
-                back.age += 1;
-            }
+            let presented = match front {
+                Some(front) => {
+                    // Swap front and back buffer
+                    std::mem::swap(front, back);
+
+                    front.age = 1;
+                    if back.age != 0 {
+                        back.age += 1;
+                    }
+                    front
+                }
+                None => {
+                    back.age = 1;
+                    back
+                }
+            };
 
-            front.attach(self.surface.as_ref().unwrap());
+            presented.attach(self.surface.as_ref().unwrap());
 
             // Like Mesa's EGL/WSI implementation, we damage the whole buffer with `i32::MAX` if
             // the compositor doesn't support `damage_buffer`.
             // https://bugs.freedesktop.org/show_bug.cgi?id=78190
-            if self.surface().version() < 4 {
+            // The same is done when the scale changed, since the whole surface changes size.
+            //
+            // Empty damage means that nothing changed, so the buffers are swapped without
+            // damaging anything.
+            if damage.is_empty() && !self.damage_all {
+                // Nothing to damage.
+            } else if self.surface().version() < 4 || self.damage_all {
                 self.surface().damage(0, 0, i32::MAX, i32::MAX);
             } else {
                 for rect in damage {
@@ -133,6 +259,7 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> WaylandImpl<D, W> {
             }
 
             self.surface().commit();
+            self.damage_all = false;
         }
 
         let _ = self
@@ -156,28 +283,12 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W>
         Self: 'a;
 
     fn new(window: W, display: &Arc<WaylandDisplayImpl<D>>) -> Result<Self, InitError<W>> {
-        // Get the raw Wayland window.
-        let raw = window.window_handle()?.as_raw();
-        let RawWindowHandle::Wayland(w) = raw else {
-            return Err(InitError::Unsupported(window));
+        let surface = match surface_proxy(&window, display) {
+            Ok(surface) => surface,
+            Err(InitError::Unsupported(())) => return Err(InitError::Unsupported(window)),
+            Err(InitError::Failure(err)) => return Err(InitError::Failure(err)),
         };
-
-        let surface_id = unsafe {
-            ObjectId::from_ptr(
-                wl_surface::WlSurface::interface(),
-                w.surface.as_ptr().cast(),
-            )
-        }
-        .swbuf_err("Failed to create proxy for surface ID.")?;
-        let surface = wl_surface::WlSurface::from_id(display.conn(), surface_id)
-            .swbuf_err("Failed to create proxy for surface ID.")?;
-        Ok(Self {
-            display: display.clone(),
-            surface: Some(surface),
-            buffers: Default::default(),
-            size: None,
-            window_handle: window,
-        })
+        Ok(Self::with_surface(window, display, surface, None))
     }
 
     #[inline]
@@ -185,6 +296,67 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W>
         &self.window_handle
     }
 
+    fn buffer_count(&self) -> u8 {
+        if self.single_buffer {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn set_buffer_count(&mut self, count: u8) -> Result<(), SoftBufferError> {
+        match count {
+            1 => {
+                self.single_buffer = true;
+                // Keep the buffer on screen, the other one isn't needed anymore.
+                if let Some((front @ Some(_), back)) = &mut self.buffers {
+                    *back = front.take().unwrap();
+                }
+            }
+            // The front buffer is allocated again in `buffer_mut`.
+            2 => self.single_buffer = false,
+            _ => return Err(SoftBufferError::Unimplemented),
+        }
+        Ok(())
+    }
+
+    fn supports_partial_present(&self) -> bool {
+        // `wl_surface.damage_buffer` was introduced in version 4.
+        self.surface().version() >= 4
+    }
+
+    fn replace_window(&mut self, window: W) -> Result<(), InitError<W>> {
+        let surface = match surface_proxy(&window, &self.display) {
+            Ok(surface) => surface,
+            Err(InitError::Unsupported(())) => return Err(InitError::Unsupported(window)),
+            Err(InitError::Failure(err)) => return Err(InitError::Failure(err)),
+        };
+        // A subsurface moves over to the new window at the same position.
+        let (surface, subsurface) = match self.subsurface.as_ref().map(|&(_, position)| position) {
+            Some(position) => {
+                let (child, subsurface) = create_subsurface(&self.display, &surface, position)?;
+                self.destroy_subsurface();
+                (child, Some((subsurface, position)))
+            }
+            None => (surface, None),
+        };
+        if self.scale != 1 && surface.version() >= 3 {
+            surface.set_buffer_scale(self.scale);
+        }
+
+        // The buffers may still be attached to the old surface, and never be released.
+        self.buffers = None;
+        // The old surface has to be dropped before the window it borrows.
+        self.surface = Some(surface);
+        self.subsurface = subsurface;
+        self.window_handle = window;
+        Ok(())
+    }
+
+    fn uses_shared_memory(&self) -> bool {
+        true
+    }
+
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
         self.size = Some(
             (|| {
@@ -197,12 +369,145 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W>
         Ok(())
     }
 
+    fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+        let max = NonZeroU32::new(i32::MAX as u32).unwrap();
+        (max, max)
+    }
+
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        self.size
+            .map(|(width, height)| (width.try_into().unwrap(), height.try_into().unwrap()))
+    }
+
+    fn set_opaque_region(&mut self, rects: &[Rect]) -> Result<(), SoftBufferError> {
+        if rects.is_empty() {
+            self.surface().set_opaque_region(None);
+            return Ok(());
+        }
+
+        let compositor = self
+            .display
+            .compositor
+            .as_ref()
+            .ok_or(SoftBufferError::Unimplemented)?;
+        let region = compositor.create_region(&self.display.qh, ());
+        for rect in rects {
+            let clamp = |v: u32| i32::try_from(v).unwrap_or(i32::MAX);
+            region.add(
+                clamp(rect.x),
+                clamp(rect.y),
+                clamp(rect.width.get()),
+                clamp(rect.height.get()),
+            );
+        }
+
+        // The region is double-buffered state, so it's applied on the next commit.
+        self.surface().set_opaque_region(Some(&region));
+        region.destroy();
+
+        Ok(())
+    }
+
+    fn set_buffer_scale(&mut self, scale: i32) -> Result<(), SoftBufferError> {
+        if scale == self.scale {
+            return Ok(());
+        }
+
+        // Introduced in version 3, it is an error to use this request in version 2 or lower.
+        if self.surface().version() < 3 {
+            return Err(SoftBufferError::Unimplemented);
+        }
+
+        // The scale is double-buffered state, so it's applied on the next commit.
+        self.surface().set_buffer_scale(scale);
+        self.scale = scale;
+        self.damage_all = true;
+
+        Ok(())
+    }
+
+    fn set_content_scale(&mut self, scale: Option<f64>) -> Result<(), SoftBufferError> {
+        // Without a scale, the compositor scales the buffer to the surface as it sees fit.
+        let scale = scale.unwrap_or(1.0);
+
+        // Fractional scales would need `wp_viewporter`.
+        if scale.fract() != 0.0 || scale > i32::MAX as f64 {
+            return Err(SoftBufferError::Unimplemented);
+        }
+
+        self.set_buffer_scale(scale as i32)
+    }
+
+    fn set_present_timeout(&mut self, timeout: Option<Duration>) {
+        self.present_timeout = timeout;
+    }
+
+    fn set_present_stats(&mut self, enabled: bool) {
+        self.present_stats = enabled.then_some(None);
+    }
+
+    fn last_present_stats(&self) -> Option<PresentStats> {
+        self.present_stats.flatten()
+    }
+
+    fn flush(&mut self) -> Result<(), SoftBufferError> {
+        self.display
+            .event_queue
+            .lock()
+            .unwrap_or_else(|x| x.into_inner())
+            .flush()
+            .swbuf_err("Failed to flush Wayland connection")
+    }
+
+    fn buffer_ready(&mut self) -> Result<bool, SoftBufferError> {
+        let Some((_front, back)) = &self.buffers else {
+            return Ok(true);
+        };
+        if back.released() {
+            return Ok(true);
+        }
+
+        // Handle the events that already arrived, without waiting for more.
+        let mut event_queue = self
+            .display
+            .event_queue
+            .lock()
+            .unwrap_or_else(|x| x.into_inner());
+        match dispatch_until(&mut event_queue, Instant::now()) {
+            Ok(()) | Err(SoftBufferError::Timeout) => {}
+            Err(err) => return Err(err),
+        }
+
+        Ok(back.released())
+    }
+
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
-        let (width, height) = self
-            .size
-            .expect("Must set size of surface before calling `buffer_mut()`");
+        let (width, height) = self.size.ok_or(SoftBufferError::SizeNotSet)?;
+
+        // Waiting for the compositor to release a buffer of a destroyed surface could block
+        // forever. This is only noticed for surfaces that we created.
+        if !self.surface().is_alive() {
+            return Err(SoftBufferError::WindowClosed);
+        }
+
+        let start = self.present_stats.map(|_| {
+            let in_flight = self.buffers.as_ref().map_or(0, |(front, back)| {
+                u8::from(front.as_ref().is_some_and(|front| !front.released()))
+                    + u8::from(!back.released())
+            });
+            (Instant::now(), in_flight)
+        });
+
+        if let Some((front, back)) = &mut self.buffers {
+            if front.is_none() && !self.single_buffer {
+                *front = Some(WaylandBuffer::new(
+                    &self.display.shm,
+                    width.get(),
+                    height.get(),
+                    &self.display.qh,
+                ));
+            }
 
-        if let Some((_front, back)) = &mut self.buffers {
             // Block if back buffer not released yet
             if !back.released() {
                 let mut event_queue = self
@@ -210,13 +515,16 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W>
                     .event_queue
                     .lock()
                     .unwrap_or_else(|x| x.into_inner());
+                let deadline = self.present_timeout.map(|timeout| Instant::now() + timeout);
                 while !back.released() {
-                    event_queue.blocking_dispatch(&mut State).map_err(|err| {
-                        SoftBufferError::PlatformError(
-                            Some("Wayland dispatch failure".to_string()),
-                            Some(Box::new(err)),
-                        )
-                    })?;
+                    match deadline {
+                        Some(deadline) => dispatch_until(&mut event_queue, deadline)?,
+                        None => {
+                            event_queue
+                                .blocking_dispatch(&mut State)
+                                .map_err(dispatch_error)?;
+                        }
+                    }
                 }
             }
 
@@ -224,13 +532,16 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W>
             back.resize(width.get(), height.get());
         } else {
             // Allocate front and back buffer
-            self.buffers = Some((
+            let front = (!self.single_buffer).then(|| {
                 WaylandBuffer::new(
                     &self.display.shm,
                     width.get(),
                     height.get(),
                     &self.display.qh,
-                ),
+                )
+            });
+            self.buffers = Some((
+                front,
                 WaylandBuffer::new(
                     &self.display.shm,
                     width.get(),
@@ -240,18 +551,49 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W>
             ));
         };
 
+        if let Some((start, in_flight)) = start {
+            self.present_stats = Some(Some(PresentStats {
+                wait: start.elapsed(),
+                in_flight,
+            }));
+        }
+
         let age = self.buffers.as_mut().unwrap().1.age;
         Ok(BufferImpl {
             stack: util::BorrowStack::new(self, |buffer| {
                 Ok(unsafe { buffer.buffers.as_mut().unwrap().1.mapped_mut() })
             })?,
+            width: width.try_into().unwrap(),
+            height: height.try_into().unwrap(),
             age,
         })
     }
 }
 
+impl<D: ?Sized, W: ?Sized> WaylandImpl<D, W> {
+    /// Destroy the surface and subsurface that we created, if any.
+    fn destroy_subsurface(&mut self) {
+        if let Some((subsurface, _)) = self.subsurface.take() {
+            subsurface.destroy();
+            if let Some(surface) = &self.surface {
+                surface.destroy();
+            }
+        }
+    }
+}
+
 impl<D: ?Sized, W: ?Sized> Drop for WaylandImpl<D, W> {
     fn drop(&mut self) {
+        self.destroy_subsurface();
+
+        // Make sure the last commit reaches the compositor.
+        let _ = self
+            .display
+            .event_queue
+            .lock()
+            .unwrap_or_else(|x| x.into_inner())
+            .flush();
+
         // Make sure the surface is dropped first.
         self.surface = None;
     }
@@ -259,10 +601,22 @@ impl<D: ?Sized, W: ?Sized> Drop for WaylandImpl<D, W> {
 
 pub struct BufferImpl<'a, D: ?Sized, W> {
     stack: util::BorrowStack<'a, WaylandImpl<D, W>, [u32]>,
+    width: NonZeroU32,
+    height: NonZeroU32,
     age: u8,
 }
 
 impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> BufferInterface for BufferImpl<'_, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        self.height
+    }
+
     #[inline]
     fn pixels(&self) -> &[u32] {
         self.stack.member()
@@ -281,11 +635,29 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> BufferInterface for Buffe
         self.stack.into_container().present_with_damage(damage)
     }
 
+    fn present_with_damage_reported(self, damage: &[Rect]) -> Result<Vec<Rect>, SoftBufferError> {
+        let (width, height) = (self.width, self.height);
+        let imp = self.stack.into_container();
+        // This mirrors the decision in `WaylandImpl::present_with_damage`.
+        let reported = if damage.is_empty() && !imp.damage_all {
+            Vec::new()
+        } else if imp.surface().version() < 4 || imp.damage_all {
+            vec![Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }]
+        } else {
+            damage.to_vec()
+        };
+        imp.present_with_damage(damage)?;
+        Ok(reported)
+    }
+
     fn present(self) -> Result<(), SoftBufferError> {
         let imp = self.stack.into_container();
-        let (width, height) = imp
-            .size
-            .expect("Must set size of surface before calling `present()`");
+        let (width, height) = imp.size.ok_or(SoftBufferError::SizeNotSet)?;
         imp.present_with_damage(&[Rect {
             x: 0,
             y: 0,
@@ -296,6 +668,200 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> BufferInterface for Buffe
     }
 }
 
+/// Like `EventQueue::blocking_dispatch`, but returns `SoftBufferError::Timeout` if no events
+/// arrived before `deadline`.
+fn dispatch_until(
+    event_queue: &mut EventQueue<State>,
+    deadline: Instant,
+) -> Result<(), SoftBufferError> {
+    if event_queue
+        .dispatch_pending(&mut State)
+        .map_err(dispatch_error)?
+        > 0
+    {
+        return Ok(());
+    }
+
+    event_queue
+        .flush()
+        .swbuf_err("Failed to flush Wayland connection")?;
+
+    if let Some(guard) = event_queue.prepare_read() {
+        loop {
+            // Round up, so we don't spin when less than a millisecond is left.
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let timeout = (timeout.as_micros() + 999) / 1000;
+            let timeout = i32::try_from(timeout).unwrap_or(i32::MAX);
+
+            let fd = guard.connection_fd();
+            match poll(&mut [PollFd::new(&fd, PollFlags::IN)], timeout) {
+                Ok(0) => return Err(SoftBufferError::Timeout),
+                Ok(_) => break,
+                Err(Errno::INTR) => continue,
+                Err(err) => {
+                    return Err(io::Error::from(err)).swbuf_err("Failed to poll Wayland connection")
+                }
+            }
+        }
+
+        match guard.read() {
+            Ok(_) => {}
+            Err(WaylandError::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err).swbuf_err("Failed to read Wayland events"),
+        }
+    }
+
+    event_queue
+        .dispatch_pending(&mut State)
+        .map_err(dispatch_error)?;
+
+    Ok(())
+}
+
+/// Create a proxy for the `wl_surface` of a window.
+fn surface_proxy<D: HasDisplayHandle + ?Sized, W: HasWindowHandle>(
+    window: &W,
+    display: &WaylandDisplayImpl<D>,
+) -> Result<wl_surface::WlSurface, InitError<()>> {
+    // Get the raw Wayland window.
+    let raw = window.window_handle()?.as_raw();
+    let RawWindowHandle::Wayland(w) = raw else {
+        return Err(InitError::Unsupported(()));
+    };
+
+    let surface_id = unsafe {
+        ObjectId::from_ptr(
+            wl_surface::WlSurface::interface(),
+            w.surface.as_ptr().cast(),
+        )
+    }
+    .swbuf_err("Failed to create proxy for surface ID.")?;
+    let surface = wl_surface::WlSurface::from_id(display.conn(), surface_id)
+        .swbuf_err("Failed to create proxy for surface ID.")?;
+    Ok(surface)
+}
+
+/// Create a new surface as a subsurface of `parent`, at `position` relative to it.
+fn create_subsurface<D: ?Sized>(
+    display: &WaylandDisplayImpl<D>,
+    parent: &wl_surface::WlSurface,
+    (x, y): (i32, i32),
+) -> Result<(wl_surface::WlSurface, wl_subsurface::WlSubsurface), SoftBufferError> {
+    let (Some(compositor), Some(subcompositor)) = (&display.compositor, &display.subcompositor)
+    else {
+        return Err(SoftBufferError::Unimplemented);
+    };
+
+    let surface = compositor.create_surface(&display.qh, ());
+    let subsurface = subcompositor.get_subsurface(&surface, parent, &display.qh, ());
+    subsurface.set_position(x, y);
+    // Show presented frames without waiting for the parent surface to be committed.
+    subsurface.set_desync();
+    Ok((surface, subsurface))
+}
+
+/// Extension methods for the Wayland platform on [`Context`](crate::Context).
+///
+/// These return nothing if the context doesn't use Wayland, e.g. because it was created for an
+/// X11 display.
+pub trait ContextExtWayland {
+    /// The `wl_shm` pixel formats that the compositor supports, as the values of the
+    /// `wl_shm.format` enum.
+    ///
+    /// These are DRM fourcc codes, except for `0` (ARGB8888) and `1` (XRGB8888), which every
+    /// compositor supports. Softbuffer itself always uses XRGB8888.
+    fn supported_shm_formats(&self) -> Vec<u32>;
+}
+
+impl<D: HasDisplayHandle> ContextExtWayland for crate::Context<D> {
+    fn supported_shm_formats(&self) -> Vec<u32> {
+        match &self.context_impl {
+            crate::ContextDispatch::Wayland(display) => display
+                .shm_formats
+                .iter()
+                .map(|&format| format.into())
+                .collect(),
+            #[allow(unreachable_patterns)]
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Extension methods for the Wayland platform on [`Surface`](crate::Surface).
+pub trait SurfaceExtWayland<D, W>: Sized {
+    /// Creates a new surface that draws to a `wl_subsurface` of the window's surface, instead of
+    /// to the window's surface itself.
+    ///
+    /// This allows drawing e.g. an overlay with softbuffer on top of a window that is rendered
+    /// with OpenGL or Vulkan. The subsurface is placed at `position` relative to the top-left
+    /// corner of the window's surface, and is in desynchronized mode, so presenting shows the
+    /// new contents without committing the window's surface.
+    ///
+    /// If the context doesn't use Wayland, this returns
+    /// [`SoftBufferError::UnsupportedWindowPlatform`]. If the compositor doesn't support
+    /// subsurfaces, this returns [`SoftBufferError::Unimplemented`].
+    fn new_subsurface(
+        context: &crate::Context<D>,
+        window: W,
+        position: (i32, i32),
+    ) -> Result<Self, SoftBufferError>;
+
+    /// Move a surface created with [`SurfaceExtWayland::new_subsurface`] to `(x, y)` relative to
+    /// the window's surface.
+    ///
+    /// Like for every subsurface, the new position takes effect when the window's surface is
+    /// committed next. Returns [`SoftBufferError::Unimplemented`] if this isn't such a surface.
+    fn set_subsurface_position(&mut self, x: i32, y: i32) -> Result<(), SoftBufferError>;
+
+    /// Whether the compositor supports `wl_surface.damage_buffer`.
+    ///
+    /// Without it, every present damages the whole surface, so fine-grained damage is wasted
+    /// effort. Returns `false` if the surface doesn't use Wayland.
+    fn supports_buffer_damage(&self) -> bool;
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceExtWayland<D, W> for crate::Surface<D, W> {
+    fn new_subsurface(
+        context: &crate::Context<D>,
+        window: W,
+        position: (i32, i32),
+    ) -> Result<Self, SoftBufferError> {
+        let surface = match &context.context_impl {
+            crate::ContextDispatch::Wayland(display) => {
+                WaylandImpl::new_subsurface(window, display, position)
+                    .map(crate::SurfaceDispatch::Wayland)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(InitError::Unsupported(window)),
+        };
+
+        Self::from_init(context, surface)
+    }
+
+    fn set_subsurface_position(&mut self, x: i32, y: i32) -> Result<(), SoftBufferError> {
+        match &mut *self.surface_impl {
+            crate::SurfaceDispatch::Wayland(imp) => imp.set_subsurface_position(x, y),
+            #[allow(unreachable_patterns)]
+            _ => Err(SoftBufferError::Unimplemented),
+        }
+    }
+
+    fn supports_buffer_damage(&self) -> bool {
+        match &*self.surface_impl {
+            crate::SurfaceDispatch::Wayland(imp) => imp.supports_partial_present(),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+fn dispatch_error(err: DispatchError) -> SoftBufferError {
+    SoftBufferError::PlatformError(
+        Some("Wayland dispatch failure".to_string()),
+        Some(Box::new(err)),
+    )
+}
+
 impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
     fn event(
         _: &mut State,
@@ -309,11 +875,77 @@ impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
     }
 }
 
-impl Dispatch<wl_shm::WlShm, ()> for State {
+impl Dispatch<wl_shm::WlShm, Arc<Mutex<Vec<wl_shm::Format>>>> for State {
     fn event(
         _: &mut State,
         _: &wl_shm::WlShm,
-        _: wl_shm::Event,
+        event: wl_shm::Event,
+        formats: &Arc<Mutex<Vec<wl_shm::Format>>>,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        if let wl_shm::Event::Format {
+            format: WEnum::Value(format),
+        } = event
+        {
+            formats.lock().unwrap().push(format);
+        }
+    }
+}
+
+impl Dispatch<wl_compositor::WlCompositor, ()> for State {
+    fn event(
+        _: &mut State,
+        _: &wl_compositor::WlCompositor,
+        _: wl_compositor::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_region::WlRegion, ()> for State {
+    fn event(
+        _: &mut State,
+        _: &wl_region::WlRegion,
+        _: wl_region::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_subcompositor::WlSubcompositor, ()> for State {
+    fn event(
+        _: &mut State,
+        _: &wl_subcompositor::WlSubcompositor,
+        _: wl_subcompositor::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_subsurface::WlSubsurface, ()> for State {
+    fn event(
+        _: &mut State,
+        _: &wl_subsurface::WlSubsurface,
+        _: wl_subsurface::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_surface::WlSurface, ()> for State {
+    fn event(
+        _: &mut State,
+        _: &wl_surface::WlSurface,
+        _: wl_surface::Event,
         _: &(),
         _: &Connection,
         _: &QueueHandle<State>,
@@ -2,25 +2,28 @@
 
 #![allow(clippy::uninlined_format_args)]
 
-use js_sys::Object;
+use js_sys::{Object, Reflect, Uint8ClampedArray};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::{JsCast, JsValue};
-use web_sys::ImageData;
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use web_sys::{ImageBitmap, ImageData};
 use web_sys::{OffscreenCanvas, OffscreenCanvasRenderingContext2d};
 
 use crate::backend_interface::*;
 use crate::error::{InitError, SwResultExt};
 use crate::{util, NoDisplayHandle, NoWindowHandle, Rect, SoftBufferError};
 use std::marker::PhantomData;
+use std::mem;
 use std::num::NonZeroU32;
 
 /// Display implementation for the web platform.
 ///
 /// This just caches the document to prevent having to query it every time.
+#[derive(Clone)]
 pub struct WebDisplayImpl<D> {
     document: web_sys::Document,
-    _display: D,
+    display: D,
 }
 
 impl<D: HasDisplayHandle> ContextInterface<D> for WebDisplayImpl<D> {
@@ -35,10 +38,11 @@ impl<D: HasDisplayHandle> ContextInterface<D> for WebDisplayImpl<D> {
             .document()
             .swbuf_err("`Document` is not present in this runtime")?;
 
-        Ok(Self {
-            document,
-            _display: display,
-        })
+        Ok(Self { document, display })
+    }
+
+    fn display(&self) -> &D {
+        &self.display
     }
 }
 
@@ -49,6 +53,15 @@ pub struct WebImpl<D, W> {
     /// The buffer that we're drawing to.
     buffer: Vec<u32>,
 
+    /// The `ImageData` we upload to the canvas, together with its backing array.
+    ///
+    /// This is kept around between frames so that only the damaged parts of it have to be
+    /// rewritten. It is reset when the canvas is resized.
+    image_data: Option<(Uint8ClampedArray, ImageData)>,
+
+    /// Scratch space used to convert a row of the buffer to RGBA.
+    row: Vec<u8>,
+
     /// Buffer has been presented.
     buffer_presented: bool,
 
@@ -75,13 +88,67 @@ enum Canvas {
     },
 }
 
+/// Attributes for the 2D context of a canvas.
+///
+/// See [`SurfaceExtWeb::from_canvas_with_options`]. These only take effect if the canvas doesn't
+/// have a 2D context yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CanvasContextOptions {
+    /// Whether the canvas has an alpha channel. Softbuffer always draws opaque pixels, so
+    /// turning this off can let the browser skip blending the canvas with the page.
+    ///
+    /// Defaults to `true`.
+    pub alpha: bool,
+    /// Whether the contents are read back often. This makes [`Surface::fetch`] much faster, at
+    /// the cost of drawing without the GPU.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`Surface::fetch`]: crate::Surface::fetch
+    pub will_read_frequently: bool,
+}
+
+impl Default for CanvasContextOptions {
+    fn default() -> Self {
+        Self {
+            alpha: true,
+            will_read_frequently: false,
+        }
+    }
+}
+
+impl CanvasContextOptions {
+    fn to_js(self) -> Object {
+        let options = Object::new();
+        // Setting a property on a plain object can't fail.
+        Reflect::set(&options, &"alpha".into(), &self.alpha.into()).unwrap();
+        Reflect::set(
+            &options,
+            &"willReadFrequently".into(),
+            &self.will_read_frequently.into(),
+        )
+        .unwrap();
+        options
+    }
+}
+
 impl<D: HasDisplayHandle, W: HasWindowHandle> WebImpl<D, W> {
-    fn from_canvas(canvas: HtmlCanvasElement, window: W) -> Result<Self, SoftBufferError> {
-        let ctx = Self::resolve_ctx(canvas.get_context("2d").ok(), "CanvasRenderingContext2d")?;
+    fn from_canvas(
+        canvas: HtmlCanvasElement,
+        options: Option<CanvasContextOptions>,
+        window: W,
+    ) -> Result<Self, SoftBufferError> {
+        let ctx = match options {
+            Some(options) => canvas.get_context_with_context_options("2d", &options.to_js()),
+            None => canvas.get_context("2d"),
+        };
+        let ctx = Self::resolve_ctx(ctx.ok(), "CanvasRenderingContext2d")?;
 
         Ok(Self {
             canvas: Canvas::Canvas { canvas, ctx },
             buffer: Vec::new(),
+            image_data: None,
+            row: Vec::new(),
             buffer_presented: false,
             size: None,
             window_handle: window,
@@ -89,15 +156,22 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> WebImpl<D, W> {
         })
     }
 
-    fn from_offscreen_canvas(canvas: OffscreenCanvas, window: W) -> Result<Self, SoftBufferError> {
-        let ctx = Self::resolve_ctx(
-            canvas.get_context("2d").ok(),
-            "OffscreenCanvasRenderingContext2d",
-        )?;
+    fn from_offscreen_canvas(
+        canvas: OffscreenCanvas,
+        options: Option<CanvasContextOptions>,
+        window: W,
+    ) -> Result<Self, SoftBufferError> {
+        let ctx = match options {
+            Some(options) => canvas.get_context_with_context_options("2d", &options.to_js()),
+            None => canvas.get_context("2d"),
+        };
+        let ctx = Self::resolve_ctx(ctx.ok(), "OffscreenCanvasRenderingContext2d")?;
 
         Ok(Self {
             canvas: Canvas::OffscreenCanvas { canvas, ctx },
             buffer: Vec::new(),
+            image_data: None,
+            row: Vec::new(),
             buffer_presented: false,
             size: None,
             window_handle: window,
@@ -121,9 +195,7 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> WebImpl<D, W> {
     }
 
     fn present_with_damage(&mut self, damage: &[Rect]) -> Result<(), SoftBufferError> {
-        let (buffer_width, _buffer_height) = self
-            .size
-            .expect("Must set size of surface before calling `present_with_damage()`");
+        let (buffer_width, buffer_height) = self.size.ok_or(SoftBufferError::SizeNotSet)?;
 
         let union_damage = if let Some(rect) = util::union_damage(damage) {
             rect
@@ -131,67 +203,51 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> WebImpl<D, W> {
             return Ok(());
         };
 
-        // Create a bitmap from the buffer.
-        let bitmap: Vec<_> = self
-            .buffer
-            .chunks_exact(buffer_width.get() as usize)
-            .skip(union_damage.y as usize)
-            .take(union_damage.height.get() as usize)
-            .flat_map(|row| {
-                row.iter()
-                    .skip(union_damage.x as usize)
-                    .take(union_damage.width.get() as usize)
-            })
-            .copied()
-            .flat_map(|pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 255])
-            .collect();
-
-        debug_assert_eq!(
-            bitmap.len() as u32,
-            union_damage.width.get() * union_damage.height.get() * 4
-        );
-
-        #[cfg(target_feature = "atomics")]
-        #[allow(non_local_definitions)]
-        let result = {
-            // When using atomics, the underlying memory becomes `SharedArrayBuffer`,
-            // which can't be shared with `ImageData`.
-            use js_sys::{Uint8Array, Uint8ClampedArray};
-            use wasm_bindgen::prelude::wasm_bindgen;
-
-            #[wasm_bindgen]
-            extern "C" {
-                #[wasm_bindgen(js_name = ImageData)]
-                type ImageDataExt;
-
-                #[wasm_bindgen(catch, constructor, js_class = ImageData)]
-                fn new(array: Uint8ClampedArray, sw: u32) -> Result<ImageDataExt, JsValue>;
-            }
-
-            let array = Uint8Array::new_with_length(bitmap.len() as u32);
-            array.copy_from(&bitmap);
-            let array = Uint8ClampedArray::new(&array);
-            ImageDataExt::new(array, union_damage.width.get())
+        let (array, image_data) = self.image_data.get_or_insert_with(|| {
+            let array = Uint8ClampedArray::new_with_length(
+                (total_len(buffer_width.get(), buffer_height.get()) * 4) as u32,
+            );
+            // The `ImageData` shares its storage with `array`, so writes to `array` show up in
+            // the `ImageData` without having to recreate it.
+            // This should only throw an error if the array's size is incorrect.
+            let image_data = ImageDataExt::new(&array, buffer_width.get())
                 .map(JsValue::from)
                 .map(ImageData::unchecked_from_js)
-        };
-        #[cfg(not(target_feature = "atomics"))]
-        let result = ImageData::new_with_u8_clamped_array(
-            wasm_bindgen::Clamped(&bitmap),
-            union_damage.width.get(),
-        );
-        // This should only throw an error if the buffer we pass's size is incorrect.
-        let image_data = result.unwrap();
+                .unwrap();
+            (array, image_data)
+        });
+
+        // Only convert the rows and columns that were damaged.
+        let x = union_damage.x as usize;
+        let width = union_damage.width.get() as usize;
+        for y in union_damage.y..union_damage.y + union_damage.height.get() {
+            let start = y as usize * buffer_width.get() as usize + x;
+            let pixels = &self.buffer[start..start + width];
+
+            self.row.clear();
+            self.row.extend(
+                pixels
+                    .iter()
+                    .flat_map(|&pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8, 255]),
+            );
+
+            // When using atomics, the underlying memory becomes `SharedArrayBuffer`, which can't
+            // be shared with `ImageData`. Since `array` is not backed by Wasm memory, copying
+            // into it works either way.
+            array
+                .subarray((start * 4) as u32, ((start + width) * 4) as u32)
+                .copy_from(&self.row);
+        }
 
         for rect in damage {
             // This can only throw an error if `data` is detached, which is impossible.
             self.canvas
                 .put_image_data(
-                    &image_data,
-                    union_damage.x.into(),
-                    union_damage.y.into(),
-                    (rect.x - union_damage.x).into(),
-                    (rect.y - union_damage.y).into(),
+                    image_data,
+                    0.0,
+                    0.0,
+                    rect.x.into(),
+                    rect.y.into(),
                     rect.width.get().into(),
                     rect.height.get().into(),
                 )
@@ -202,6 +258,30 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> WebImpl<D, W> {
 
         Ok(())
     }
+
+    fn present_to_bitmap(&mut self) -> Result<ImageBitmap, SoftBufferError> {
+        let Canvas::OffscreenCanvas { canvas, ctx } = &self.canvas else {
+            return Err(SoftBufferError::PlatformError(
+                Some(
+                    "Only surfaces using an `OffscreenCanvas` can present to an `ImageBitmap`"
+                        .into(),
+                ),
+                None,
+            ));
+        };
+
+        // Transferring leaves the canvas blank, so the damage of earlier presents is lost and the
+        // whole image has to be put again. `image_data` is kept up to date by every present.
+        if let Some((_, image_data)) = &self.image_data {
+            // This can only throw an error if `data` is detached, which is impossible.
+            ctx.put_image_data(image_data, 0.0, 0.0).unwrap();
+        }
+
+        canvas
+            .transfer_to_image_bitmap()
+            .ok()
+            .swbuf_err("Failed to transfer the `OffscreenCanvas` to an `ImageBitmap`")
+    }
 }
 
 impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for WebImpl<D, W> {
@@ -232,12 +312,13 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for WebImpl
                 let value: &JsValue = unsafe { handle.obj.cast().as_ref() };
                 let canvas: OffscreenCanvas = value.clone().unchecked_into();
 
-                return Self::from_offscreen_canvas(canvas, window).map_err(InitError::Failure);
+                return Self::from_offscreen_canvas(canvas, None, window)
+                    .map_err(InitError::Failure);
             }
             _ => return Err(InitError::Unsupported(window)),
         };
 
-        Self::from_canvas(canvas, window).map_err(InitError::Failure)
+        Self::from_canvas(canvas, None, window).map_err(InitError::Failure)
     }
 
     /// Get the inner window handle.
@@ -246,12 +327,17 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for WebImpl
         &self.window_handle
     }
 
+    fn supports_partial_present(&self) -> bool {
+        true
+    }
+
     /// De-duplicates the error handling between `HtmlCanvasElement` and `OffscreenCanvas`.
     /// Resize the canvas to the given dimensions.
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
         if self.size != Some((width, height)) {
             self.buffer_presented = false;
-            self.buffer.resize(total_len(width.get(), height.get()), 0);
+            util::resize_buffer(&mut self.buffer, total_len(width.get(), height.get()));
+            self.image_data = None;
             self.canvas.set_width(width.get());
             self.canvas.set_height(height.get());
             self.size = Some((width, height));
@@ -260,14 +346,47 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for WebImpl
         Ok(())
     }
 
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        self.size
+    }
+
+    fn resize_preserving(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        let old = match self.size {
+            Some((old_width, _)) if self.buffer_presented && self.size != Some((width, height)) => {
+                Some((old_width, mem::take(&mut self.buffer)))
+            }
+            _ => None,
+        };
+
+        self.resize(width, height)?;
+
+        if let Some((old_width, old)) = old {
+            util::copy_overlapping(
+                &old,
+                old_width.get() as usize,
+                &mut self.buffer,
+                width.get() as usize,
+            );
+            self.buffer_presented = true;
+        }
+
+        Ok(())
+    }
+
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
+        if self.size.is_none() {
+            return Err(SoftBufferError::SizeNotSet);
+        }
+
         Ok(BufferImpl { imp: self })
     }
 
-    fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
-        let (width, height) = self
-            .size
-            .expect("Must set size of surface before calling `fetch()`");
+    fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+        let (width, height) = self.size.ok_or(SoftBufferError::SizeNotSet)?;
 
         let image_data = self
             .canvas
@@ -276,6 +395,29 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for WebImpl
             // TODO: Can also error if width or height are 0.
             .swbuf_err("`Canvas` contains pixels from a different origin")?;
 
+        out.clear();
+        out.extend(
+            image_data
+                .data()
+                .0
+                .chunks_exact(4)
+                .map(|chunk| u32::from_be_bytes([0, chunk[0], chunk[1], chunk[2]])),
+        );
+        Ok(())
+    }
+
+    fn fetch_rect(&mut self, rect: Rect) -> Result<Vec<u32>, SoftBufferError> {
+        let image_data = self
+            .canvas
+            .get_image_data(
+                rect.x.into(),
+                rect.y.into(),
+                rect.width.get().into(),
+                rect.height.get().into(),
+            )
+            .ok()
+            .swbuf_err("`Canvas` contains pixels from a different origin")?;
+
         Ok(image_data
             .data()
             .0
@@ -285,6 +427,17 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for WebImpl
     }
 }
 
+#[wasm_bindgen]
+extern "C" {
+    /// `ImageData`, with a constructor taking a [`Uint8ClampedArray`] that `web-sys` doesn't
+    /// expose in all the versions we support.
+    #[wasm_bindgen(js_name = ImageData)]
+    type ImageDataExt;
+
+    #[wasm_bindgen(catch, constructor, js_class = ImageData)]
+    fn new(array: &Uint8ClampedArray, sw: u32) -> Result<ImageDataExt, JsValue>;
+}
+
 /// Extension methods for the Wasm target on [`Surface`](crate::Surface).
 pub trait SurfaceExtWeb: Sized {
     /// Creates a new instance of this struct, using the provided [`HtmlCanvasElement`].
@@ -299,28 +452,81 @@ pub trait SurfaceExtWeb: Sized {
     /// # Errors
     /// If a another context then "2d" was already created for this canvas.
     fn from_offscreen_canvas(offscreen_canvas: OffscreenCanvas) -> Result<Self, SoftBufferError>;
+
+    /// Like [`SurfaceExtWeb::from_canvas`], but creates the 2D context with the given options.
+    ///
+    /// In particular, [`CanvasContextOptions::will_read_frequently`] speeds up
+    /// [`Surface::fetch`](crate::Surface::fetch).
+    ///
+    /// # Errors
+    /// The same as for [`SurfaceExtWeb::from_canvas`].
+    fn from_canvas_with_options(
+        canvas: HtmlCanvasElement,
+        options: CanvasContextOptions,
+    ) -> Result<Self, SoftBufferError>;
+
+    /// Like [`SurfaceExtWeb::from_offscreen_canvas`], but creates the 2D context with the given
+    /// options.
+    ///
+    /// # Errors
+    /// The same as for [`SurfaceExtWeb::from_offscreen_canvas`].
+    fn from_offscreen_canvas_with_options(
+        offscreen_canvas: OffscreenCanvas,
+        options: CanvasContextOptions,
+    ) -> Result<Self, SoftBufferError>;
+
+    /// Transfers the contents that were last presented to a new [`ImageBitmap`].
+    ///
+    /// This is meant for rendering in a Web Worker: present a [`Buffer`](crate::Buffer) as usual,
+    /// then call this and post the bitmap to the main thread, which can show it with
+    /// `ImageBitmapRenderingContext.transferFromImageBitmap()`. The main thread doesn't have to
+    /// do any work for the frame besides that.
+    ///
+    /// # Errors
+    /// If the surface wasn't created with [`SurfaceExtWeb::from_offscreen_canvas`].
+    fn present_to_bitmap(&mut self) -> Result<ImageBitmap, SoftBufferError>;
 }
 
 impl SurfaceExtWeb for crate::Surface<NoDisplayHandle, NoWindowHandle> {
     fn from_canvas(canvas: HtmlCanvasElement) -> Result<Self, SoftBufferError> {
-        let imple = crate::SurfaceDispatch::Web(WebImpl::from_canvas(canvas, NoWindowHandle(()))?);
-
-        Ok(Self {
-            surface_impl: Box::new(imple),
-            _marker: PhantomData,
-        })
+        let imple = WebImpl::from_canvas(canvas, None, NoWindowHandle(()))?;
+        Ok(Self::from_web_impl(imple))
     }
 
     fn from_offscreen_canvas(offscreen_canvas: OffscreenCanvas) -> Result<Self, SoftBufferError> {
-        let imple = crate::SurfaceDispatch::Web(WebImpl::from_offscreen_canvas(
-            offscreen_canvas,
-            NoWindowHandle(()),
-        )?);
+        let imple = WebImpl::from_offscreen_canvas(offscreen_canvas, None, NoWindowHandle(()))?;
+        Ok(Self::from_web_impl(imple))
+    }
 
-        Ok(Self {
-            surface_impl: Box::new(imple),
-            _marker: PhantomData,
-        })
+    fn from_canvas_with_options(
+        canvas: HtmlCanvasElement,
+        options: CanvasContextOptions,
+    ) -> Result<Self, SoftBufferError> {
+        let imple = WebImpl::from_canvas(canvas, Some(options), NoWindowHandle(()))?;
+        Ok(Self::from_web_impl(imple))
+    }
+
+    fn from_offscreen_canvas_with_options(
+        offscreen_canvas: OffscreenCanvas,
+        options: CanvasContextOptions,
+    ) -> Result<Self, SoftBufferError> {
+        let imple =
+            WebImpl::from_offscreen_canvas(offscreen_canvas, Some(options), NoWindowHandle(()))?;
+        Ok(Self::from_web_impl(imple))
+    }
+
+    fn present_to_bitmap(&mut self) -> Result<ImageBitmap, SoftBufferError> {
+        match &mut *self.surface_impl {
+            crate::SurfaceDispatch::Web(imp) => imp.present_to_bitmap(),
+            #[allow(unreachable_patterns)]
+            _ => Err(SoftBufferError::Unimplemented),
+        }
+    }
+}
+
+impl crate::Surface<NoDisplayHandle, NoWindowHandle> {
+    fn from_web_impl(imple: WebImpl<NoDisplayHandle, NoWindowHandle>) -> Self {
+        Self::from_dispatch(crate::SurfaceDispatch::Web(imple))
     }
 }
 
@@ -377,6 +583,20 @@ pub struct BufferImpl<'a, D, W> {
 }
 
 impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_, D, W> {
+    fn width(&self) -> NonZeroU32 {
+        self.imp
+            .size
+            .expect("Must set size of surface before calling `width()` on the buffer")
+            .0
+    }
+
+    fn height(&self) -> NonZeroU32 {
+        self.imp
+            .size
+            .expect("Must set size of surface before calling `height()` on the buffer")
+            .1
+    }
+
     fn pixels(&self) -> &[u32] {
         &self.imp.buffer
     }
@@ -395,10 +615,7 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_,
 
     /// Push the buffer to the canvas.
     fn present(self) -> Result<(), SoftBufferError> {
-        let (width, height) = self
-            .imp
-            .size
-            .expect("Must set size of surface before calling `present()`");
+        let (width, height) = self.imp.size.ok_or(SoftBufferError::SizeNotSet)?;
         self.imp.present_with_damage(&[Rect {
             x: 0,
             y: 0,
@@ -410,6 +627,15 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_,
     fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError> {
         self.imp.present_with_damage(damage)
     }
+
+    fn present_and_fetch(self) -> Result<Vec<u32>, PresentAndFetchError> {
+        // `putImageData` is synchronous, so there is nothing to wait for.
+        let imp = self.imp;
+        BufferImpl { imp: &mut *imp }
+            .present()
+            .map_err(PresentAndFetchError::Present)?;
+        imp.fetch().map_err(PresentAndFetchError::Fetch)
+    }
 }
 
 #[inline(always)]
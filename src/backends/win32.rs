@@ -3,20 +3,27 @@
 //! This module converts the input buffer into a bitmap and then stretches it to the window.
 
 use crate::backend_interface::*;
-use crate::{Rect, SoftBufferError};
+use crate::{util, NoDisplayHandle, NoWindowHandle, Rect, SoftBufferError};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 
+use std::ffi::OsStr;
 use std::io;
 use std::marker::PhantomData;
 use std::mem;
-use std::num::{NonZeroI32, NonZeroU32};
+use std::num::{NonZeroI32, NonZeroIsize, NonZeroU32};
+use std::os::windows::ffi::OsStrExt;
 use std::ptr::{self, NonNull};
 use std::slice;
 use std::sync::{mpsc, Mutex, OnceLock};
 use std::thread;
 
-use windows_sys::Win32::Foundation::HWND;
+use windows_sys::Win32::Foundation::{
+    CloseHandle, SetLastError, ERROR_ALREADY_EXISTS, HANDLE, HWND, INVALID_HANDLE_VALUE, POINT,
+    SIZE,
+};
 use windows_sys::Win32::Graphics::Gdi;
+use windows_sys::Win32::System::Memory::{CreateFileMappingW, PAGE_READWRITE};
+use windows_sys::Win32::UI::WindowsAndMessaging as Wm;
 
 const ZERO_QUAD: Gdi::RGBQUAD = Gdi::RGBQUAD {
     rgbBlue: 0,
@@ -28,9 +35,13 @@ const ZERO_QUAD: Gdi::RGBQUAD = Gdi::RGBQUAD {
 struct Buffer {
     dc: Gdi::HDC,
     bitmap: Gdi::HBITMAP,
+    /// The file mapping that holds the pixels, or null if the system allocated them.
+    section: HANDLE,
     pixels: NonNull<u32>,
     width: NonZeroI32,
     height: NonZeroI32,
+    /// Whether the rows are shown bottom to top.
+    bottom_up: bool,
     presented: bool,
 }
 
@@ -40,6 +51,9 @@ impl Drop for Buffer {
     fn drop(&mut self) {
         unsafe {
             Gdi::DeleteObject(self.bitmap);
+            if !self.section.is_null() {
+                CloseHandle(self.section);
+            }
         }
 
         Allocator::get().deallocate(self.dc);
@@ -47,7 +61,51 @@ impl Drop for Buffer {
 }
 
 impl Buffer {
-    fn new(window_dc: Gdi::HDC, width: NonZeroI32, height: NonZeroI32) -> Self {
+    /// Create a new buffer, whose pixels live in a new file mapping called `section_name` (a
+    /// nul-terminated wide string) if given.
+    ///
+    /// The rows of a `bottom_up` buffer are shown bottom to top.
+    fn new(
+        window_dc: Gdi::HDC,
+        width: NonZeroI32,
+        height: NonZeroI32,
+        bottom_up: bool,
+        section_name: Option<&[u16]>,
+    ) -> Result<Self, SoftBufferError> {
+        let section = match section_name {
+            Some(name) => {
+                let size = width.get() as u64 * height.get() as u64 * 4;
+                unsafe { SetLastError(0) };
+                let section = unsafe {
+                    CreateFileMappingW(
+                        INVALID_HANDLE_VALUE,
+                        ptr::null(),
+                        PAGE_READWRITE,
+                        (size >> 32) as u32,
+                        size as u32,
+                        name.as_ptr(),
+                    )
+                };
+                let err = io::Error::last_os_error();
+                if section.is_null() {
+                    return Err(SoftBufferError::PlatformError(
+                        Some("Failed to create file mapping".into()),
+                        Some(Box::new(err)),
+                    ));
+                }
+                if err.raw_os_error() == Some(ERROR_ALREADY_EXISTS as i32) {
+                    // Someone else's mapping may be too small, or in use for something else.
+                    unsafe { CloseHandle(section) };
+                    return Err(SoftBufferError::PlatformError(
+                        Some("A file mapping with this name already exists".into()),
+                        Some(Box::new(err)),
+                    ));
+                }
+                section
+            }
+            None => ptr::null_mut(),
+        };
+
         let dc = Allocator::get().allocate(window_dc);
         assert!(!dc.is_null());
 
@@ -56,7 +114,12 @@ impl Buffer {
             bmi_header: Gdi::BITMAPINFOHEADER {
                 biSize: mem::size_of::<Gdi::BITMAPINFOHEADER>() as u32,
                 biWidth: width.get(),
-                biHeight: -height.get(),
+                // A positive height makes a bottom-up bitmap.
+                biHeight: if bottom_up {
+                    height.get()
+                } else {
+                    -height.get()
+                },
                 biPlanes: 1,
                 biBitCount: 32,
                 biCompression: Gdi::BI_BITFIELDS,
@@ -83,7 +146,6 @@ impl Buffer {
         };
 
         // XXX alignment?
-        // XXX better to use CreateFileMapping, and pass hSection?
         // XXX test return value?
         let mut pixels: *mut u32 = ptr::null_mut();
         let bitmap = unsafe {
@@ -92,7 +154,7 @@ impl Buffer {
                 &bitmap_info as *const BitmapInfo as *const _,
                 Gdi::DIB_RGB_COLORS,
                 &mut pixels as *mut *mut u32 as _,
-                ptr::null_mut(),
+                section,
                 0,
             )
         };
@@ -103,14 +165,16 @@ impl Buffer {
             Gdi::SelectObject(dc, bitmap);
         }
 
-        Self {
+        Ok(Self {
             dc,
             bitmap,
+            section,
             width,
             height,
+            bottom_up,
             pixels,
             presented: false,
-        }
+        })
     }
 
     #[inline]
@@ -137,7 +201,9 @@ impl Buffer {
 /// The handle to a window for software buffering.
 pub struct Win32Impl<D: ?Sized, W> {
     /// The window handle.
-    window: OnlyUsedFromOrigin<HWND>,
+    ///
+    /// This is `None` if we draw to a device context supplied by the user, which we don't own.
+    window: OnlyUsedFromOrigin<Option<HWND>>,
 
     /// The device context for the window.
     dc: OnlyUsedFromOrigin<Gdi::HDC>,
@@ -145,6 +211,15 @@ pub struct Win32Impl<D: ?Sized, W> {
     /// The buffer used to hold the image.
     buffer: Option<Buffer>,
 
+    /// Whether the window is layered, and presented with `UpdateLayeredWindow`.
+    alpha_blending: bool,
+
+    /// The name of the file mapping to allocate the buffer in, as a nul-terminated wide string.
+    shared_section: Option<Vec<u16>>,
+
+    /// Whether buffers are created bottom-up.
+    vertical_flip: bool,
+
     /// The handle for the window.
     ///
     /// This should be kept alive in order to keep `window` valid.
@@ -158,8 +233,10 @@ pub struct Win32Impl<D: ?Sized, W> {
 
 impl<D: ?Sized, W> Drop for Win32Impl<D, W> {
     fn drop(&mut self) {
-        // Release our resources.
-        Allocator::get().release(self.window.0, self.dc.0);
+        // Release our resources. A user-supplied device context is not ours to release.
+        if let Some(window) = self.window.0 {
+            Allocator::get().release(window, self.dc.0);
+        }
     }
 }
 
@@ -171,8 +248,74 @@ struct BitmapInfo {
 }
 
 impl<D: HasDisplayHandle, W: HasWindowHandle> Win32Impl<D, W> {
+    fn set_shared_section(&mut self, name: Option<&str>) -> Result<(), SoftBufferError> {
+        if name.is_some_and(|name| name.contains('\0')) {
+            return Err(SoftBufferError::PlatformError(
+                Some("File mapping names can't contain nul characters".into()),
+                None,
+            ));
+        }
+        self.shared_section =
+            name.map(|name| OsStr::new(name).encode_wide().chain(Some(0)).collect());
+
+        // Move the buffer into the new file mapping.
+        if let Some(buffer) = self.buffer.take() {
+            let (width, height) = (buffer.width, buffer.height);
+            drop(buffer);
+            self.buffer = Some(Buffer::new(
+                self.dc.0,
+                width,
+                height,
+                self.vertical_flip,
+                self.shared_section.as_deref(),
+            )?);
+        }
+
+        Ok(())
+    }
+
     fn present_with_damage(&mut self, damage: &[Rect]) -> Result<(), SoftBufferError> {
         let buffer = self.buffer.as_mut().unwrap();
+
+        if self.alpha_blending && !damage.is_empty() {
+            let size = SIZE {
+                cx: buffer.width.get(),
+                cy: buffer.height.get(),
+            };
+            let origin = POINT { x: 0, y: 0 };
+            let blend = Gdi::BLENDFUNCTION {
+                BlendOp: Gdi::AC_SRC_OVER as u8,
+                BlendFlags: 0,
+                SourceConstantAlpha: 255,
+                AlphaFormat: Gdi::AC_SRC_ALPHA as u8,
+            };
+
+            // Layered windows are always updated as a whole.
+            let window = self.window.0.unwrap();
+            let result = unsafe {
+                Wm::UpdateLayeredWindow(
+                    window,
+                    ptr::null_mut(),
+                    ptr::null(),
+                    &size,
+                    buffer.dc,
+                    &origin,
+                    0,
+                    &blend,
+                    Wm::ULW_ALPHA,
+                )
+            };
+            if result == 0 {
+                return Err(SoftBufferError::PlatformError(
+                    Some("Failed to update layered window".into()),
+                    Some(Box::new(io::Error::last_os_error())),
+                ));
+            }
+            buffer.presented = true;
+
+            return Ok(());
+        }
+
         unsafe {
             for rect in damage.iter().copied() {
                 let (x, y, width, height) = (|| {
@@ -184,6 +327,12 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> Win32Impl<D, W> {
                     ))
                 })()
                 .ok_or(SoftBufferError::DamageOutOfRange { rect })?;
+                // The device contexts count rows from the top, not in the order of the bitmap.
+                let y = if buffer.bottom_up {
+                    buffer.height.get() - y - height
+                } else {
+                    y
+                };
                 Gdi::BitBlt(
                     self.dc.0,
                     x,
@@ -198,7 +347,9 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> Win32Impl<D, W> {
             }
 
             // Validate the window.
-            Gdi::ValidateRect(self.window.0, ptr::null_mut());
+            if let Some(window) = self.window.0 {
+                Gdi::ValidateRect(window, ptr::null_mut());
+            }
         }
         buffer.presented = true;
 
@@ -206,6 +357,26 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> Win32Impl<D, W> {
     }
 }
 
+impl<D: HasDisplayHandle> Win32Impl<D, NoWindowHandle> {
+    /// Create a new `Win32Impl` that draws to the given device context.
+    ///
+    /// # Safety
+    ///
+    /// The device context must be valid for the lifetime of the surface.
+    unsafe fn from_hdc(hdc: NonZeroIsize) -> Self {
+        Self {
+            dc: (hdc.get() as Gdi::HDC).into(),
+            window: None.into(),
+            buffer: None,
+            alpha_blending: false,
+            shared_section: None,
+            vertical_flip: false,
+            handle: NoWindowHandle(()),
+            _display: PhantomData,
+        }
+    }
+}
+
 impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Win32Impl<D, W> {
     type Context = D;
     type Buffer<'a>
@@ -236,8 +407,11 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Win32Im
 
         Ok(Self {
             dc: dc.into(),
-            window: hwnd.into(),
+            window: Some(hwnd).into(),
             buffer: None,
+            alpha_blending: false,
+            shared_section: None,
+            vertical_flip: false,
             handle: window,
             _display: PhantomData,
         })
@@ -248,6 +422,69 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Win32Im
         &self.handle
     }
 
+    fn supports_partial_present(&self) -> bool {
+        // Layered windows are always updated as a whole.
+        !self.alpha_blending
+    }
+
+    fn uses_shared_memory(&self) -> bool {
+        true
+    }
+
+    fn set_alpha_blending(&mut self, enabled: bool) -> Result<(), SoftBufferError> {
+        if self.alpha_blending == enabled {
+            return Ok(());
+        }
+        let Some(window) = self.window.0 else {
+            // Only windows can be layered.
+            return Err(SoftBufferError::Unimplemented);
+        };
+
+        // Extended styles fit in 32 bits, so this works on 32- and 64-bit Windows alike.
+        let style = unsafe { Wm::GetWindowLongW(window, Wm::GWL_EXSTYLE) } as u32;
+        let style = if enabled {
+            style | Wm::WS_EX_LAYERED
+        } else {
+            style & !Wm::WS_EX_LAYERED
+        };
+        unsafe { SetLastError(0) };
+        let previous = unsafe { Wm::SetWindowLongW(window, Wm::GWL_EXSTYLE, style as i32) };
+        if previous == 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(0) {
+                return Err(SoftBufferError::PlatformError(
+                    Some("Failed to change the layered style of the window".into()),
+                    Some(Box::new(err)),
+                ));
+            }
+        }
+
+        self.alpha_blending = enabled;
+        Ok(())
+    }
+
+    fn set_vertical_flip(&mut self, flip: bool) -> Result<(), SoftBufferError> {
+        if self.vertical_flip == flip {
+            return Ok(());
+        }
+        self.vertical_flip = flip;
+
+        // The orientation of a bitmap is fixed when it is created.
+        if let Some(buffer) = self.buffer.take() {
+            let (width, height) = (buffer.width, buffer.height);
+            drop(buffer);
+            self.buffer = Some(Buffer::new(
+                self.dc.0,
+                width,
+                height,
+                flip,
+                self.shared_section.as_deref(),
+            )?);
+        }
+
+        Ok(())
+    }
+
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
         let (width, height) = (|| {
             let width = NonZeroI32::try_from(width).ok()?;
@@ -262,28 +499,240 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceInterface<D, W> for Win32Im
             }
         }
 
-        self.buffer = Some(Buffer::new(self.dc.0, width, height));
+        // A named file mapping can't be created again while the old buffer still has it open.
+        if self.shared_section.is_some() {
+            self.buffer = None;
+        }
+
+        self.buffer = Some(Buffer::new(
+            self.dc.0,
+            width,
+            height,
+            self.vertical_flip,
+            self.shared_section.as_deref(),
+        )?);
+
+        Ok(())
+    }
+
+    fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+        let max = NonZeroU32::new(i32::MAX as u32).unwrap();
+        (max, max)
+    }
+
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        let buffer = self.buffer.as_ref()?;
+        Some((
+            buffer.width.try_into().unwrap(),
+            buffer.height.try_into().unwrap(),
+        ))
+    }
+
+    fn resize_preserving(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        let mut old = match self.buffer.take() {
+            Some(buffer)
+                if buffer.presented
+                    && (buffer.width.get() as u32, buffer.height.get() as u32)
+                        != (width.get(), height.get()) =>
+            {
+                Some(buffer)
+            }
+            buffer => {
+                self.buffer = buffer;
+                None
+            }
+        };
+
+        // The old buffer has to be closed before its file mapping can be created again.
+        let saved = if self.shared_section.is_some() {
+            old.take()
+                .map(|old| (old.width, old.bottom_up, old.pixels().to_vec()))
+        } else {
+            None
+        };
+
+        if let Err(err) = self.resize(width, height) {
+            if old.is_some() {
+                self.buffer = old;
+            }
+            return Err(err);
+        }
+
+        let old = old
+            .as_ref()
+            .map(|old| (old.width, old.bottom_up, old.pixels()))
+            .or_else(|| {
+                saved
+                    .as_ref()
+                    .map(|(width, bottom_up, pixels)| (*width, *bottom_up, &pixels[..]))
+            });
+        if let (Some((old_width, old_bottom_up, old_pixels)), Some(buffer)) =
+            (old, self.buffer.as_mut())
+        {
+            // Keep the top-left region in place on screen, which is at the end of the rows of a
+            // bottom-up buffer.
+            let old_width = old_width.get() as usize;
+            let old_top_down;
+            let old_pixels = if old_bottom_up {
+                old_top_down = old_pixels
+                    .rchunks_exact(old_width)
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<_>>();
+                &old_top_down[..]
+            } else {
+                old_pixels
+            };
+            let width = buffer.width.get() as usize;
+            let bottom_up = buffer.bottom_up;
+            let pixels = buffer.pixels_mut();
+            util::copy_overlapping(old_pixels, old_width, pixels, width);
+            if bottom_up {
+                pixels.reverse();
+                pixels.chunks_exact_mut(width).for_each(<[u32]>::reverse);
+            }
+            buffer.presented = true;
+        }
+
+        Ok(())
+    }
+
+    fn present_tile(&mut self, pixels: &[u32], tile: Rect) -> Result<(), SoftBufferError> {
+        let (x, y, width, height) = (|| {
+            Some((
+                i32::try_from(tile.x).ok()?,
+                i32::try_from(tile.y).ok()?,
+                NonZeroI32::try_from(tile.width).ok()?,
+                NonZeroI32::try_from(tile.height).ok()?,
+            ))
+        })()
+        .ok_or(SoftBufferError::DamageOutOfRange { rect: tile })?;
+
+        let mut bitmap = Buffer::new(self.dc.0, width, height, false, None)?;
+        bitmap.pixels_mut().copy_from_slice(pixels);
+        unsafe {
+            Gdi::BitBlt(
+                self.dc.0,
+                x,
+                y,
+                width.get(),
+                height.get(),
+                bitmap.dc,
+                0,
+                0,
+                Gdi::SRCCOPY,
+            );
+        }
 
         Ok(())
     }
 
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
         if self.buffer.is_none() {
-            panic!("Must set size of surface before calling `buffer_mut()`");
+            return Err(SoftBufferError::SizeNotSet);
         }
 
         Ok(BufferImpl(self))
     }
 
     /// Fetch the buffer from the window.
-    fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
-        Err(SoftBufferError::Unimplemented)
+    fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+        let (width, height) = match self.buffer.as_ref() {
+            Some(buffer) => (buffer.width, buffer.height),
+            None => return Err(SoftBufferError::SizeNotSet),
+        };
+
+        // Copy the window contents into a bitmap that uses our pixel format. Like for the buffer,
+        // its device context is allocated by the allocator thread.
+        let target = Buffer::new(self.dc.0, width, height, false, None)?;
+        let result = unsafe {
+            Gdi::BitBlt(
+                target.dc,
+                0,
+                0,
+                width.get(),
+                height.get(),
+                self.dc.0,
+                0,
+                0,
+                Gdi::SRCCOPY,
+            )
+        };
+        if result == 0 {
+            return Err(SoftBufferError::PlatformError(
+                Some("Failed to copy the window contents".into()),
+                Some(Box::new(io::Error::last_os_error())),
+            ));
+        }
+
+        // Make sure GDI is done drawing to the bitmap before we read from it.
+        unsafe { Gdi::GdiFlush() };
+
+        out.clear();
+        out.extend(target.pixels().iter().map(|pixel| pixel & 0x00ff_ffff));
+        Ok(())
+    }
+
+    fn fetch_rect(&mut self, rect: Rect) -> Result<Vec<u32>, SoftBufferError> {
+        let (x, y, width, height) = (|| {
+            Some((
+                i32::try_from(rect.x).ok()?,
+                i32::try_from(rect.y).ok()?,
+                NonZeroI32::try_from(rect.width).ok()?,
+                NonZeroI32::try_from(rect.height).ok()?,
+            ))
+        })()
+        .ok_or(SoftBufferError::DamageOutOfRange { rect })?;
+
+        // Like `fetch`, but the bitmap only covers the requested region.
+        let target = Buffer::new(self.dc.0, width, height, false, None)?;
+        let result = unsafe {
+            Gdi::BitBlt(
+                target.dc,
+                0,
+                0,
+                width.get(),
+                height.get(),
+                self.dc.0,
+                x,
+                y,
+                Gdi::SRCCOPY,
+            )
+        };
+        if result == 0 {
+            return Err(SoftBufferError::PlatformError(
+                Some("Failed to copy the window contents".into()),
+                Some(Box::new(io::Error::last_os_error())),
+            ));
+        }
+
+        unsafe { Gdi::GdiFlush() };
+
+        Ok(target
+            .pixels()
+            .iter()
+            .map(|pixel| pixel & 0x00ff_ffff)
+            .collect())
     }
 }
 
 pub struct BufferImpl<'a, D, W>(&'a mut Win32Impl<D, W>);
 
 impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        self.0.buffer.as_ref().unwrap().width.try_into().unwrap()
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        self.0.buffer.as_ref().unwrap().height.try_into().unwrap()
+    }
+
     #[inline]
     fn pixels(&self) -> &[u32] {
         self.0.buffer.as_ref().unwrap().pixels()
@@ -317,6 +766,97 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> BufferInterface for BufferImpl<'_,
         let imp = self.0;
         imp.present_with_damage(damage)
     }
+
+    fn present_with_damage_reported(self, damage: &[Rect]) -> Result<Vec<Rect>, SoftBufferError> {
+        let imp = self.0;
+        let buffer = imp.buffer.as_ref().unwrap();
+        // Layered windows are always updated as a whole.
+        let reported = if imp.alpha_blending {
+            vec![Rect {
+                x: 0,
+                y: 0,
+                width: buffer.width.try_into().unwrap(),
+                height: buffer.height.try_into().unwrap(),
+            }]
+        } else {
+            damage.to_vec()
+        };
+        imp.present_with_damage(damage)?;
+        Ok(reported)
+    }
+
+    fn present_and_fetch(self) -> Result<Vec<u32>, PresentAndFetchError> {
+        // `fetch` flushes GDI before reading, so the presented image is included.
+        let imp = self.0;
+        BufferImpl(&mut *imp)
+            .present()
+            .map_err(PresentAndFetchError::Present)?;
+        imp.fetch().map_err(PresentAndFetchError::Fetch)
+    }
+}
+
+impl crate::Surface<NoDisplayHandle, NoWindowHandle> {
+    /// Creates a new surface that presents to the given device context (`HDC`).
+    ///
+    /// This is useful for drawing to something that isn't a window on its own, e.g. a memory
+    /// device context used for printing. To draw to a child control, it's simpler to create the
+    /// surface from the window handle of the control with [`Surface::new`](crate::Surface::new).
+    ///
+    /// Softbuffer never releases or deletes the device context, that is up to the caller.
+    /// [`Surface::set_alpha_blending`](crate::Surface::set_alpha_blending) is not supported
+    /// on such surfaces.
+    ///
+    /// # Safety
+    ///
+    /// `hdc` must be a valid device context that outlives the surface.
+    pub unsafe fn from_hdc(hdc: NonZeroIsize) -> Result<Self, SoftBufferError> {
+        // SAFETY: Upheld by the caller.
+        let imple = crate::SurfaceDispatch::Win32(unsafe { Win32Impl::from_hdc(hdc) });
+        Ok(Self::from_dispatch(imple))
+    }
+}
+
+/// Extension methods for the Win32 platform on [`Surface`](crate::Surface).
+pub trait SurfaceExtWin32 {
+    /// Allocate the buffer in a named file mapping, so that other processes can map the pixels
+    /// with `OpenFileMappingW`, or pass `None` to let the system allocate it again.
+    ///
+    /// The buffer is reallocated right away if the size is set, so its contents are lost. It
+    /// is also reallocated by [`Surface::resize`](crate::Surface::resize), which creates the
+    /// file mapping anew with the new size. Other processes have to open it again after that.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SoftBufferError::PlatformError`] if the file mapping can't be created, or if
+    /// another file mapping with the same name exists. Returns
+    /// [`SoftBufferError::Unimplemented`] if the surface doesn't use Win32.
+    fn set_shared_section(&mut self, name: Option<&str>) -> Result<(), SoftBufferError>;
+
+    /// The handle of the file mapping that holds the current buffer.
+    ///
+    /// Returns `None` if no shared section was set, the size wasn't set yet, or the surface
+    /// doesn't use Win32.
+    fn shared_section(&self) -> Option<NonZeroIsize>;
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceExtWin32 for crate::Surface<D, W> {
+    fn set_shared_section(&mut self, name: Option<&str>) -> Result<(), SoftBufferError> {
+        match &mut *self.surface_impl {
+            crate::SurfaceDispatch::Win32(imp) => imp.set_shared_section(name),
+            #[allow(unreachable_patterns)]
+            _ => Err(SoftBufferError::Unimplemented),
+        }
+    }
+
+    fn shared_section(&self) -> Option<NonZeroIsize> {
+        match &*self.surface_impl {
+            crate::SurfaceDispatch::Win32(imp) => {
+                NonZeroIsize::new(imp.buffer.as_ref()?.section as isize)
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
 }
 
 /// Allocator for device contexts.
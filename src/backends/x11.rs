@@ -7,10 +7,10 @@
 
 use crate::backend_interface::*;
 use crate::error::{InitError, SwResultExt};
-use crate::{Rect, SoftBufferError};
+use crate::{util, PresentStats, Rect, SoftBufferError};
 use raw_window_handle::{
-    HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle, XcbDisplayHandle,
-    XcbWindowHandle,
+    DisplayHandle, HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle,
+    XcbDisplayHandle, XcbWindowHandle,
 };
 use rustix::{
     fd::{AsFd, BorrowedFd, OwnedFd},
@@ -18,22 +18,25 @@ use rustix::{
 };
 
 use std::{
-    collections::HashSet,
+    collections::HashMap,
     fmt,
     fs::File,
     io, mem,
     num::{NonZeroU16, NonZeroU32},
     ptr::{null_mut, NonNull},
     slice,
-    sync::Arc,
+    sync::{Arc, OnceLock},
+    time::Instant,
 };
 
 use as_raw_xcb_connection::AsRawXcbConnection;
 use x11rb::connection::{Connection, SequenceNumber};
-use x11rb::cookie::Cookie;
+use x11rb::cookie::VoidCookie;
 use x11rb::errors::{ConnectionError, ReplyError, ReplyOrIdError};
 use x11rb::protocol::shm::{self, ConnectionExt as _};
 use x11rb::protocol::xproto::{self, ConnectionExt as _, ImageOrder, VisualClass, Visualid};
+use x11rb::protocol::ErrorKind;
+use x11rb::wrapper::ConnectionExt as _;
 use x11rb::xcb_ffi::XCBConnection;
 
 pub struct X11DisplayImpl<D: ?Sized> {
@@ -43,15 +46,21 @@ pub struct X11DisplayImpl<D: ?Sized> {
     /// SHM extension is available.
     is_shm_available: bool,
 
-    /// All visuals using softbuffer's pixel representation
-    supported_visuals: HashSet<Visualid>,
+    /// All visuals using softbuffer's pixel representation, mapped to their depth.
+    supported_visuals: HashMap<Visualid, u8>,
+
+    /// The supported visual that windows on the default screen should preferably use.
+    preferred_visual: Option<Visualid>,
+
+    /// The `_NET_WM_OPAQUE_REGION` atom, interned on first use.
+    opaque_region_atom: OnceLock<xproto::Atom>,
 
     /// The generic display where the `connection` field comes from.
     ///
     /// Without `&mut`, the underlying connection cannot be closed without other unsafe behavior.
     /// With `&mut`, the connection can be dropped without us knowing about it. Therefore, we
     /// cannot provide `&mut` access to this field.
-    _display: D,
+    display: D,
 }
 
 impl<D: HasDisplayHandle + ?Sized> ContextInterface<D> for Arc<X11DisplayImpl<D>> {
@@ -82,21 +91,20 @@ impl<D: HasDisplayHandle + ?Sized> ContextInterface<D> for Arc<X11DisplayImpl<D>
         };
 
         // Validate the display handle to ensure we can use it.
-        let connection = match xcb_handle.connection {
+        let (connection, screen) = match xcb_handle.connection {
             Some(connection) => {
                 // Wrap the display handle in an x11rb connection.
                 // SAFETY: We don't own the connection, so don't drop it. We also assert that the connection is valid.
                 let result =
                     unsafe { XCBConnection::from_raw_xcb_connection(connection.as_ptr(), false) };
 
-                result.swbuf_err("Failed to wrap XCB connection")?
+                let connection = result.swbuf_err("Failed to wrap XCB connection")?;
+                (connection, xcb_handle.screen as usize)
             }
             None => {
                 // The user didn't provide an XCB connection, so create our own.
                 tracing::info!("no XCB connection provided by the user, so spawning our own");
-                XCBConnection::connect(None)
-                    .swbuf_err("Failed to spawn XCB connection")?
-                    .0
+                XCBConnection::connect(None).swbuf_err("Failed to spawn XCB connection")?
             }
         };
 
@@ -106,14 +114,21 @@ impl<D: HasDisplayHandle + ?Sized> ContextInterface<D> for Arc<X11DisplayImpl<D>
         }
 
         let supported_visuals = supported_visuals(&connection);
+        let preferred_visual = preferred_visual(&connection, screen, &supported_visuals);
 
         Ok(Arc::new(X11DisplayImpl {
             connection: Some(connection),
             is_shm_available,
             supported_visuals,
-            _display: display,
+            preferred_visual,
+            opaque_region_atom: OnceLock::new(),
+            display,
         }))
     }
+
+    fn display(&self) -> &D {
+        &self.display
+    }
 }
 
 impl<D: ?Sized> X11DisplayImpl<D> {
@@ -122,6 +137,325 @@ impl<D: ?Sized> X11DisplayImpl<D> {
             .as_ref()
             .expect("X11DisplayImpl::connection() called after X11DisplayImpl::drop()")
     }
+
+    /// Get the `_NET_WM_OPAQUE_REGION` atom, interning it the first time.
+    fn opaque_region_atom(&self) -> Result<xproto::Atom, SoftBufferError> {
+        if let Some(&atom) = self.opaque_region_atom.get() {
+            return Ok(atom);
+        }
+
+        let atom = self
+            .connection()
+            .intern_atom(false, b"_NET_WM_OPAQUE_REGION")
+            .swbuf_err("Failed to send atom interning request")?
+            .reply()
+            .swbuf_err("Failed to intern `_NET_WM_OPAQUE_REGION`")?
+            .atom;
+        Ok(*self.opaque_region_atom.get_or_init(|| atom))
+    }
+}
+
+impl<'a> crate::Context<DisplayHandle<'a>> {
+    /// Creates a new context that uses an existing XCB connection to the X server.
+    ///
+    /// This accepts anything that wraps a libxcb connection, such as x11rb's `XCBConnection`, or
+    /// tiny-xlib's `Display` for Xlib. When a display handle without a connection is passed to
+    /// [`Context::new`](crate::Context::new), softbuffer opens a second connection of its own;
+    /// this makes it explicit that the application's connection is shared instead.
+    pub fn from_xcb_connection(
+        connection: &'a impl AsRawXcbConnection,
+        screen: i32,
+    ) -> Result<Self, SoftBufferError> {
+        let raw_connection = NonNull::new(connection.as_raw_xcb_connection().cast());
+        let handle = XcbDisplayHandle::new(raw_connection, screen);
+
+        // SAFETY: The connection is borrowed for `'a`, so it stays valid as long as the handle.
+        let display = unsafe { DisplayHandle::borrow_raw(RawDisplayHandle::Xcb(handle)) };
+        Self::new(display)
+    }
+}
+
+/// Extension methods for the X11 platform on [`Context`](crate::Context).
+///
+/// These return nothing if the context doesn't use X11, e.g. because it was created for a
+/// Wayland display.
+pub trait ContextExtX11 {
+    /// The IDs of all visuals that use softbuffer's pixel format, in ascending order.
+    ///
+    /// Windows must be created with one of these visuals to be usable with
+    /// [`Surface::new`](crate::Surface::new).
+    fn supported_visuals(&self) -> Vec<u32>;
+
+    /// The supported visual that new windows on the default screen should use.
+    ///
+    /// This is the root visual of the screen if it is supported, since that's what windows use
+    /// by default. Otherwise it is some other supported visual of depth 24, if there is one.
+    fn preferred_visual(&self) -> Option<u32>;
+
+    /// The depth of a supported visual, which is needed to create a window with it.
+    ///
+    /// Returns `None` if the visual isn't supported.
+    fn visual_depth(&self, visual_id: u32) -> Option<u8>;
+}
+
+impl<D: HasDisplayHandle> ContextExtX11 for crate::Context<D> {
+    fn supported_visuals(&self) -> Vec<u32> {
+        let Some(display) = x11_display(self) else {
+            return Vec::new();
+        };
+
+        let mut visuals: Vec<_> = display.supported_visuals.keys().copied().collect();
+        visuals.sort_unstable();
+        visuals
+    }
+
+    fn preferred_visual(&self) -> Option<u32> {
+        x11_display(self)?.preferred_visual
+    }
+
+    fn visual_depth(&self, visual_id: u32) -> Option<u8> {
+        x11_display(self)?
+            .supported_visuals
+            .get(&visual_id)
+            .copied()
+    }
+}
+
+/// Attributes of the graphics context that a surface presents with.
+///
+/// See [`SurfaceExtX11::new_with_gc_options`].
+///
+/// ```
+/// # use softbuffer::{GcFunction, GcOptions};
+/// let options = GcOptions::default()
+///     .function(GcFunction::Xor)
+///     .include_inferiors(true);
+/// assert_eq!(options.function, GcFunction::Xor);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct GcOptions {
+    /// Whether the X server sends `GraphicsExposure` events for areas of the window that
+    /// couldn't be drawn to.
+    ///
+    /// Defaults to `false`.
+    pub graphics_exposures: bool,
+    /// The logical function used to combine the buffer with the window.
+    ///
+    /// Defaults to [`GcFunction::Copy`].
+    pub function: GcFunction,
+    /// Whether drawing also covers the child windows of the window.
+    ///
+    /// Defaults to `false`.
+    pub include_inferiors: bool,
+    /// A 1-bit pixmap that masks which pixels of the window are drawn to, so that only those are
+    /// updated when presenting.
+    ///
+    /// The mask has to stay alive for as long as the surface is used. Defaults to `None`.
+    pub clip_mask: Option<NonZeroU32>,
+    /// The position of [`GcOptions::clip_mask`] relative to the window.
+    ///
+    /// Defaults to `(0, 0)`.
+    pub clip_origin: (i16, i16),
+}
+
+impl Default for GcOptions {
+    fn default() -> Self {
+        Self {
+            graphics_exposures: false,
+            function: GcFunction::Copy,
+            include_inferiors: false,
+            clip_mask: None,
+            clip_origin: (0, 0),
+        }
+    }
+}
+
+/// The logical function that combines the presented pixels (`src`) with the window (`dst`).
+///
+/// These are the `GX*` functions of the X protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GcFunction {
+    /// `0`
+    Clear,
+    /// `src & dst`
+    And,
+    /// `src & !dst`
+    AndReverse,
+    /// `src`
+    Copy,
+    /// `!src & dst`
+    AndInverted,
+    /// `dst`
+    NoOp,
+    /// `src ^ dst`
+    Xor,
+    /// `src | dst`
+    Or,
+    /// `!src & !dst`
+    Nor,
+    /// `!src ^ dst`
+    Equiv,
+    /// `!dst`
+    Invert,
+    /// `src | !dst`
+    OrReverse,
+    /// `!src`
+    CopyInverted,
+    /// `!src | dst`
+    OrInverted,
+    /// `!src | !dst`
+    Nand,
+    /// `1`
+    Set,
+}
+
+impl From<GcFunction> for xproto::GX {
+    fn from(function: GcFunction) -> Self {
+        match function {
+            GcFunction::Clear => xproto::GX::CLEAR,
+            GcFunction::And => xproto::GX::AND,
+            GcFunction::AndReverse => xproto::GX::AND_REVERSE,
+            GcFunction::Copy => xproto::GX::COPY,
+            GcFunction::AndInverted => xproto::GX::AND_INVERTED,
+            GcFunction::NoOp => xproto::GX::NOOP,
+            GcFunction::Xor => xproto::GX::XOR,
+            GcFunction::Or => xproto::GX::OR,
+            GcFunction::Nor => xproto::GX::NOR,
+            GcFunction::Equiv => xproto::GX::EQUIV,
+            GcFunction::Invert => xproto::GX::INVERT,
+            GcFunction::OrReverse => xproto::GX::OR_REVERSE,
+            GcFunction::CopyInverted => xproto::GX::COPY_INVERTED,
+            GcFunction::OrInverted => xproto::GX::OR_INVERTED,
+            GcFunction::Nand => xproto::GX::NAND,
+            GcFunction::Set => xproto::GX::SET,
+        }
+    }
+}
+
+impl GcOptions {
+    /// Set [`GcOptions::graphics_exposures`].
+    pub fn graphics_exposures(mut self, graphics_exposures: bool) -> Self {
+        self.graphics_exposures = graphics_exposures;
+        self
+    }
+
+    /// Set [`GcOptions::function`].
+    pub fn function(mut self, function: GcFunction) -> Self {
+        self.function = function;
+        self
+    }
+
+    /// Set [`GcOptions::include_inferiors`].
+    pub fn include_inferiors(mut self, include_inferiors: bool) -> Self {
+        self.include_inferiors = include_inferiors;
+        self
+    }
+
+    /// Set [`GcOptions::clip_mask`].
+    pub fn clip_mask(mut self, clip_mask: Option<NonZeroU32>) -> Self {
+        self.clip_mask = clip_mask;
+        self
+    }
+
+    /// Set [`GcOptions::clip_origin`].
+    pub fn clip_origin(mut self, x: i16, y: i16) -> Self {
+        self.clip_origin = (x, y);
+        self
+    }
+
+    fn to_aux(self) -> xproto::CreateGCAux {
+        let subwindow_mode = if self.include_inferiors {
+            xproto::SubwindowMode::INCLUDE_INFERIORS
+        } else {
+            xproto::SubwindowMode::CLIP_BY_CHILDREN
+        };
+        xproto::CreateGCAux::new()
+            .graphics_exposures(u32::from(self.graphics_exposures))
+            .function(xproto::GX::from(self.function))
+            .subwindow_mode(subwindow_mode)
+            .clip_mask(self.clip_mask.map_or(x11rb::NONE, NonZeroU32::get))
+            .clip_x_origin(i32::from(self.clip_origin.0))
+            .clip_y_origin(i32::from(self.clip_origin.1))
+    }
+}
+
+/// Extension methods for the X11 platform on [`Surface`](crate::Surface).
+pub trait SurfaceExtX11<D, W>: Sized {
+    /// Creates a new surface for the window, which uses the given visual.
+    ///
+    /// This overrides the visual in the window handle. Since the visual is known, softbuffer
+    /// doesn't have to ask the X server for it, which saves a round trip when the window handle
+    /// has no visual, e.g. for some Xlib handles.
+    ///
+    /// The visual must be one of [`ContextExtX11::supported_visuals`], otherwise an error is
+    /// returned. It also has to match the window, but that isn't checked, since it would need the
+    /// round trip that this avoids. If the context doesn't use X11, this returns
+    /// [`SoftBufferError::UnsupportedWindowPlatform`].
+    fn new_with_visual(
+        context: &crate::Context<D>,
+        window: W,
+        visual_id: u32,
+    ) -> Result<Self, SoftBufferError>;
+
+    /// Creates a new surface for the window, which presents with a graphics context that has the
+    /// given attributes.
+    ///
+    /// By default, softbuffer turns off graphics exposures and draws every pixel of the damage.
+    /// Setting a clip mask instead only updates the pixels within it, which can be cheaper than
+    /// presenting many damage rectangles for complex shapes.
+    ///
+    /// If the context doesn't use X11, this returns [`SoftBufferError::UnsupportedWindowPlatform`].
+    fn new_with_gc_options(
+        context: &crate::Context<D>,
+        window: W,
+        options: GcOptions,
+    ) -> Result<Self, SoftBufferError>;
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> SurfaceExtX11<D, W> for crate::Surface<D, W> {
+    fn new_with_visual(
+        context: &crate::Context<D>,
+        window: W,
+        visual_id: u32,
+    ) -> Result<Self, SoftBufferError> {
+        let surface = match &context.context_impl {
+            crate::ContextDispatch::X11(display) => {
+                X11Impl::new_with_visual(window, display, Some(visual_id), GcOptions::default())
+                    .map(crate::SurfaceDispatch::X11)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(InitError::Unsupported(window)),
+        };
+
+        Self::from_init(context, surface)
+    }
+
+    fn new_with_gc_options(
+        context: &crate::Context<D>,
+        window: W,
+        options: GcOptions,
+    ) -> Result<Self, SoftBufferError> {
+        let surface = match &context.context_impl {
+            crate::ContextDispatch::X11(display) => {
+                X11Impl::new_with_visual(window, display, None, options)
+                    .map(crate::SurfaceDispatch::X11)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(InitError::Unsupported(window)),
+        };
+
+        Self::from_init(context, surface)
+    }
+}
+
+/// Get the X11 display of a context, if it has one.
+fn x11_display<D: HasDisplayHandle>(context: &crate::Context<D>) -> Option<&X11DisplayImpl<D>> {
+    match &context.context_impl {
+        crate::ContextDispatch::X11(display) => Some(display),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
 }
 
 /// The handle to an X11 drawing context.
@@ -144,12 +478,22 @@ pub struct X11Impl<D: ?Sized, W: ?Sized> {
     /// The buffer we draw to.
     buffer: Buffer,
 
+    /// A shared memory segment that the X server writes to in `fetch()`, if SHM is available.
+    fetch_buffer: Option<ShmBuffer>,
+
     /// Buffer has been presented.
     buffer_presented: bool,
 
     /// The current buffer width/height.
     size: Option<(NonZeroU16, NonZeroU16)>,
 
+    /// The number of pixels in each row of the buffer.
+    stride: u16,
+
+    /// The measurements of the last `buffer_mut`, if they are turned on. `None` inside means that
+    /// nothing was measured yet.
+    present_stats: Option<Option<PresentStats>>,
+
     /// Keep the window alive.
     window_handle: W,
 }
@@ -167,33 +511,33 @@ struct ShmBuffer {
     /// The shared memory segment, paired with its ID.
     seg: Option<(ShmSegment, shm::Seg)>,
 
-    /// A cookie indicating that the shared memory segment is ready to be used.
+    /// Whether the X server is only allowed to read from the segment.
+    read_only: bool,
+
+    /// The last `shm::PutImage` request, whose completion means that the shared memory segment is
+    /// ready to be used.
     ///
     /// We can't soundly read from or write to the SHM segment until the X server is done processing the
-    /// `shm::PutImage` request. However, the X server handles requests in order, which means that, if
-    /// we send a very small request after the `shm::PutImage` request, then the X server will have to
-    /// process that request before it can process the `shm::PutImage` request. Therefore, we can use
-    /// the reply to that small request to determine when the `shm::PutImage` request is done.
+    /// `shm::PutImage` request. Checking the request for an error waits for the X server to process
+    /// it, which also tells us whether the window still exists.
     ///
-    /// In this case, we use `GetInputFocus` since it is a very small request.
-    ///
-    /// We store the sequence number instead of the `Cookie` since we cannot hold a self-referential
-    /// reference to the `connection` field.
+    /// We store the sequence number instead of the `VoidCookie` since we cannot hold a
+    /// self-referential reference to the `connection` field.
     done_processing: Option<SequenceNumber>,
 }
 
-impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> for X11Impl<D, W> {
-    type Context = Arc<X11DisplayImpl<D>>;
-    type Buffer<'a>
-        = BufferImpl<'a, D, W>
-    where
-        Self: 'a;
-
-    /// Create a new `X11Impl` from a `HasWindowHandle`.
-    fn new(window_src: W, display: &Arc<X11DisplayImpl<D>>) -> Result<Self, InitError<W>> {
+impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> X11Impl<D, W> {
+    /// Create a new `X11Impl` from a `HasWindowHandle`, optionally overriding the visual of the
+    /// window.
+    fn new_with_visual(
+        window_src: W,
+        display: &Arc<X11DisplayImpl<D>>,
+        visual_override: Option<Visualid>,
+        gc_options: GcOptions,
+    ) -> Result<Self, InitError<W>> {
         // Get the underlying raw window handle.
         let raw = window_src.window_handle()?.as_raw();
-        let window_handle = match raw {
+        let mut window_handle = match raw {
             RawWindowHandle::Xcb(xcb) => xcb,
             RawWindowHandle::Xlib(xlib) => {
                 let window = NonZeroU32::new(xlib.window as u32)
@@ -207,6 +551,11 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
             }
         };
 
+        if let Some(visual_id) = visual_override {
+            // The visual is known, so there's no need to ask the server for it.
+            window_handle.visual_id = NonZeroU32::new(visual_id);
+        }
+
         tracing::trace!("new: window_handle={:X}", window_handle.window);
         let window = window_handle.window.get();
 
@@ -238,11 +587,7 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
             .swbuf_err("Failed to generate GC ID")?;
         display
             .connection()
-            .create_gc(
-                gc,
-                window,
-                &xproto::CreateGCAux::new().graphics_exposures(0),
-            )
+            .create_gc(gc, window, &gc_options.to_aux())
             .swbuf_err("Failed to send GC creation request")?
             .check()
             .swbuf_err("Failed to create GC")?;
@@ -266,7 +611,7 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
             (geometry_reply, visual_id)
         };
 
-        if !display.supported_visuals.contains(&visual_id) {
+        if !display.supported_visuals.contains_key(&visual_id) {
             return Err(SoftBufferError::PlatformError(
                 Some(format!(
                     "Visual 0x{visual_id:x} does not use softbuffer's pixel format and is unsupported"
@@ -277,15 +622,23 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
         }
 
         // See if SHM is available.
-        let buffer = if display.is_shm_available {
+        let (buffer, fetch_buffer) = if display.is_shm_available {
             // SHM is available.
-            Buffer::Shm(ShmBuffer {
-                seg: None,
-                done_processing: None,
-            })
+            (
+                Buffer::Shm(ShmBuffer {
+                    seg: None,
+                    read_only: true,
+                    done_processing: None,
+                }),
+                Some(ShmBuffer {
+                    seg: None,
+                    read_only: false,
+                    done_processing: None,
+                }),
+            )
         } else {
             // SHM is not available.
-            Buffer::Wire(Vec::new())
+            (Buffer::Wire(Vec::new()), None)
         };
 
         Ok(Self {
@@ -295,23 +648,65 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
             depth: geometry_reply.depth,
             visual_id,
             buffer,
+            fetch_buffer,
             buffer_presented: false,
             size: None,
+            stride: 0,
+            present_stats: None,
             window_handle: window_src,
         })
     }
+}
+
+impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> for X11Impl<D, W> {
+    type Context = Arc<X11DisplayImpl<D>>;
+    type Buffer<'a>
+        = BufferImpl<'a, D, W>
+    where
+        Self: 'a;
+
+    /// Create a new `X11Impl` from a `HasWindowHandle`.
+    fn new(window_src: W, display: &Arc<X11DisplayImpl<D>>) -> Result<Self, InitError<W>> {
+        Self::new_with_visual(window_src, display, None, GcOptions::default())
+    }
 
     #[inline]
     fn window(&self) -> &W {
         &self.window_handle
     }
 
+    fn supports_partial_present(&self) -> bool {
+        true
+    }
+
+    fn uses_shared_memory(&self) -> bool {
+        matches!(self.buffer, Buffer::Shm(_))
+    }
+
+    fn set_present_stats(&mut self, enabled: bool) {
+        self.present_stats = enabled.then_some(None);
+    }
+
+    fn last_present_stats(&self) -> Option<PresentStats> {
+        self.present_stats.flatten()
+    }
+
     fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
+        self.resize_with_stride(width, height, width)
+    }
+
+    fn resize_with_stride(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+        stride: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
         tracing::trace!(
-            "resize: window={:X}, size={}x{}",
+            "resize: window={:X}, size={}x{}, stride={}",
             self.window,
             width,
-            height
+            height,
+            stride
         );
 
         // Width and height should fit in u16.
@@ -322,38 +717,189 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
             width: width.into(),
             height,
         }))?;
+        let stride: NonZeroU16 = stride.try_into().or(Err(SoftBufferError::SizeOutOfRange {
+            width: width.into(),
+            height: height.into(),
+        }))?;
 
-        if self.size != Some((width, height)) {
+        // `PutImage` can't skip padding, only `shm::PutImage` can.
+        if stride != width && matches!(self.buffer, Buffer::Wire(_)) {
+            return Err(SoftBufferError::Unimplemented);
+        }
+
+        if self.size != Some((width, height)) || self.stride != stride.get() {
             self.buffer_presented = false;
             self.buffer
-                .resize(self.display.connection(), width.get(), height.get())
+                .resize(self.display.connection(), stride.get(), height.get())
                 .swbuf_err("Failed to resize X11 buffer")?;
 
             // We successfully resized the buffer.
             self.size = Some((width, height));
+            self.stride = stride.get();
+        }
+
+        Ok(())
+    }
+
+    fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+        let max = NonZeroU32::from(NonZeroU16::MAX);
+        (max, max)
+    }
+
+    fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        self.size
+            .map(|(width, height)| (width.into(), height.into()))
+    }
+
+    fn resize_preserving(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        let old = match self.size {
+            Some((old_width, old_height))
+                if self.buffer_presented
+                    && (u32::from(old_width.get()), u32::from(old_height.get()))
+                        != (width.get(), height.get()) =>
+            {
+                self.buffer.finish_wait(self.display.connection())?;
+
+                // SAFETY: We just waited for the X server to be done with the buffer.
+                let old = unsafe { self.buffer.buffer() }
+                    .chunks_exact(self.stride.into())
+                    .flat_map(|row| &row[..old_width.get().into()])
+                    .copied()
+                    .collect::<Vec<_>>();
+                Some((old_width, old))
+            }
+            _ => None,
+        };
+
+        self.resize(width, height)?;
+
+        if let Some((old_width, old)) = old {
+            // SAFETY: The buffer was just allocated, so the X server isn't using it.
+            util::copy_overlapping(
+                &old,
+                old_width.get().into(),
+                unsafe { self.buffer.buffer_mut() },
+                width.get() as usize,
+            );
+            self.buffer_presented = true;
         }
 
         Ok(())
     }
 
+    fn set_opaque_region(&mut self, rects: &[Rect]) -> Result<(), SoftBufferError> {
+        tracing::trace!("set_opaque_region: window={:X}", self.window);
+
+        let atom = self.display.opaque_region_atom()?;
+        let conn = self.display.connection();
+
+        let cookie = if rects.is_empty() {
+            conn.delete_property(self.window, atom)
+        } else {
+            let data: Vec<u32> = rects
+                .iter()
+                .flat_map(|rect| [rect.x, rect.y, rect.width.get(), rect.height.get()])
+                .collect();
+            conn.change_property32(
+                xproto::PropMode::REPLACE,
+                self.window,
+                atom,
+                xproto::AtomEnum::CARDINAL,
+                &data,
+            )
+        };
+
+        // Don't wait for a round trip, the property is only a hint to the compositor.
+        cookie
+            .swbuf_err("Failed to send opaque region")?
+            .ignore_error();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SoftBufferError> {
+        self.display
+            .connection()
+            .flush()
+            .swbuf_err("Failed to flush X11 connection")
+    }
+
+    fn present_tile(&mut self, pixels: &[u32], tile: Rect) -> Result<(), SoftBufferError> {
+        tracing::trace!("present_tile: window={:X}", self.window);
+
+        let (dst_x, dst_y, width, height) = (|| {
+            Some((
+                i16::try_from(tile.x).ok()?,
+                i16::try_from(tile.y).ok()?,
+                u16::try_from(tile.width.get()).ok()?,
+                u16::try_from(tile.height.get()).ok()?,
+            ))
+        })()
+        .ok_or(SoftBufferError::DamageOutOfRange { rect: tile })?;
+
+        // The tile doesn't live in shared memory, so it always goes over the wire.
+        self.display
+            .connection()
+            .put_image(
+                xproto::ImageFormat::Z_PIXMAP,
+                self.window,
+                self.gc,
+                width,
+                height,
+                dst_x,
+                dst_y,
+                0,
+                self.depth,
+                bytemuck::cast_slice(pixels),
+            )
+            .map(|c| c.ignore_error())
+            .push_err()
+            .swbuf_err("Failed to draw tile to window")
+    }
+
     fn buffer_mut(&mut self) -> Result<BufferImpl<'_, D, W>, SoftBufferError> {
         tracing::trace!("buffer_mut: window={:X}", self.window);
 
+        if self.size.is_none() {
+            return Err(SoftBufferError::SizeNotSet);
+        }
+
         // Finish waiting on the previous `shm::PutImage` request, if any.
+        let start = self
+            .present_stats
+            .map(|_| (Instant::now(), u8::from(self.buffer.is_waiting())));
         self.buffer.finish_wait(self.display.connection())?;
+        if let Some((start, in_flight)) = start {
+            self.present_stats = Some(Some(PresentStats {
+                wait: start.elapsed(),
+                in_flight,
+            }));
+        }
 
         // We can now safely call `buffer_mut` on the buffer.
         Ok(BufferImpl(self))
     }
 
-    fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
+    fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
         tracing::trace!("fetch: window={:X}", self.window);
 
-        let (width, height) = self
-            .size
-            .expect("Must set size of surface before calling `fetch()`");
+        let (width, height) = self.size.ok_or(SoftBufferError::SizeNotSet)?;
+
+        if let Some(fetch_buffer) = self.fetch_buffer.as_mut() {
+            return fetch_buffer
+                .fetch(
+                    self.display.connection(),
+                    self.window,
+                    (width.get(), height.get()),
+                    (self.depth, self.visual_id),
+                    out,
+                )
+                .window_err("Failed to fetch image from window");
+        }
 
-        // TODO: Is it worth it to do SHM here? Probably not.
         let reply = self
             .display
             .connection()
@@ -368,7 +914,49 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
             )
             .swbuf_err("Failed to send image fetching request")?
             .reply()
-            .swbuf_err("Failed to fetch image from window")?;
+            .window_err("Failed to fetch image from window")?;
+
+        if reply.depth == self.depth && reply.visual == self.visual_id {
+            out.clear();
+            out.resize(reply.data.len() / 4, 0);
+            bytemuck::cast_slice_mut::<u32, u8>(out).copy_from_slice(&reply.data);
+            Ok(())
+        } else {
+            Err(SoftBufferError::PlatformError(
+                Some("Mismatch between reply and window data".into()),
+                None,
+            ))
+        }
+    }
+
+    fn fetch_rect(&mut self, rect: Rect) -> Result<Vec<u32>, SoftBufferError> {
+        tracing::trace!("fetch_rect: window={:X}", self.window);
+
+        let (x, y, width, height) = (|| {
+            Some((
+                i16::try_from(rect.x).ok()?,
+                i16::try_from(rect.y).ok()?,
+                u16::try_from(rect.width.get()).ok()?,
+                u16::try_from(rect.height.get()).ok()?,
+            ))
+        })()
+        .ok_or(SoftBufferError::DamageOutOfRange { rect })?;
+
+        let reply = self
+            .display
+            .connection()
+            .get_image(
+                xproto::ImageFormat::Z_PIXMAP,
+                self.window,
+                x,
+                y,
+                width,
+                height,
+                u32::MAX,
+            )
+            .swbuf_err("Failed to send image fetching request")?
+            .reply()
+            .window_err("Failed to fetch image from window")?;
 
         if reply.depth == self.depth && reply.visual == self.visual_id {
             let mut out = vec![0u32; reply.data.len() / 4];
@@ -385,9 +973,22 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> SurfaceInterface<D, W> fo
 
 pub struct BufferImpl<'a, D: ?Sized, W: ?Sized>(&'a mut X11Impl<D, W>);
 
-impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle + ?Sized> BufferInterface
-    for BufferImpl<'_, D, W>
-{
+impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle> BufferInterface for BufferImpl<'_, D, W> {
+    #[inline]
+    fn width(&self) -> NonZeroU32 {
+        self.0.size.unwrap().0.into()
+    }
+
+    #[inline]
+    fn height(&self) -> NonZeroU32 {
+        self.0.size.unwrap().1.into()
+    }
+
+    #[inline]
+    fn stride(&self) -> NonZeroU32 {
+        NonZeroU32::new(self.0.stride.into()).unwrap()
+    }
+
     #[inline]
     fn pixels(&self) -> &[u32] {
         // SAFETY: We called `finish_wait` on the buffer, so it is safe to call `buffer()`.
@@ -412,9 +1013,7 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle + ?Sized> BufferInterface
     fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError> {
         let imp = self.0;
 
-        let (surface_width, surface_height) = imp
-            .size
-            .expect("Must set size of surface before calling `present_with_damage()`");
+        let (surface_width, surface_height) = imp.size.ok_or(SoftBufferError::SizeNotSet)?;
 
         tracing::trace!("present: window={:X}", imp.window);
 
@@ -423,23 +1022,51 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle + ?Sized> BufferInterface
                 // This is a suboptimal strategy, raise a stink in the debug logs.
                 tracing::debug!("Falling back to non-SHM method for window drawing.");
 
-                imp.display
-                    .connection()
-                    .put_image(
-                        xproto::ImageFormat::Z_PIXMAP,
-                        imp.window,
-                        imp.gc,
-                        surface_width.get(),
-                        surface_height.get(),
-                        0,
-                        0,
-                        0,
-                        imp.depth,
-                        bytemuck::cast_slice(wire),
-                    )
-                    .map(|c| c.ignore_error())
-                    .push_err()
-                    .swbuf_err("Failed to draw image to window")?;
+                // The whole image is sent over the connection, so only send the damaged parts.
+                let mut scratch = Vec::new();
+                for rect in damage {
+                    let (dst_x, dst_y, width, height) = (|| {
+                        Some((
+                            i16::try_from(rect.x).ok()?,
+                            i16::try_from(rect.y).ok()?,
+                            u16::try_from(rect.width.get()).ok()?,
+                            u16::try_from(rect.height.get()).ok()?,
+                        ))
+                    })()
+                    .ok_or(SoftBufferError::DamageOutOfRange { rect: *rect })?;
+
+                    // The wire buffer has no padding, so full rows are already contiguous.
+                    let surface_width = usize::from(surface_width.get());
+                    let rows = rect.y as usize..rect.y as usize + usize::from(height);
+                    let pixels = if rect.x == 0 && usize::from(width) == surface_width {
+                        &wire[rows.start * surface_width..rows.end * surface_width]
+                    } else {
+                        scratch.clear();
+                        for y in rows {
+                            let start = y * surface_width + rect.x as usize;
+                            scratch.extend_from_slice(&wire[start..start + usize::from(width)]);
+                        }
+                        &scratch[..]
+                    };
+
+                    imp.display
+                        .connection()
+                        .put_image(
+                            xproto::ImageFormat::Z_PIXMAP,
+                            imp.window,
+                            imp.gc,
+                            width,
+                            height,
+                            dst_x,
+                            dst_y,
+                            0,
+                            imp.depth,
+                            bytemuck::cast_slice(pixels),
+                        )
+                        .map(|c| c.ignore_error())
+                        .push_err()
+                        .swbuf_err("Failed to draw image to window")?;
+                }
             }
 
             Buffer::Shm(ref mut shm) => {
@@ -447,49 +1074,51 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle + ?Sized> BufferInterface
                 // SAFETY: We know that we called finish_wait() before this.
                 // Put the image into the window.
                 if let Some((_, segment_id)) = shm.seg {
-                    damage
-                        .iter()
-                        .try_for_each(|rect| {
-                            let (src_x, src_y, dst_x, dst_y, width, height) = (|| {
-                                Some((
-                                    u16::try_from(rect.x).ok()?,
-                                    u16::try_from(rect.y).ok()?,
-                                    i16::try_from(rect.x).ok()?,
-                                    i16::try_from(rect.y).ok()?,
-                                    u16::try_from(rect.width.get()).ok()?,
-                                    u16::try_from(rect.height.get()).ok()?,
-                                ))
-                            })(
+                    let mut last = None;
+                    for rect in damage {
+                        let (src_x, src_y, dst_x, dst_y, width, height) = (|| {
+                            Some((
+                                u16::try_from(rect.x).ok()?,
+                                u16::try_from(rect.y).ok()?,
+                                i16::try_from(rect.x).ok()?,
+                                i16::try_from(rect.y).ok()?,
+                                u16::try_from(rect.width.get()).ok()?,
+                                u16::try_from(rect.height.get()).ok()?,
+                            ))
+                        })(
+                        )
+                        .ok_or(SoftBufferError::DamageOutOfRange { rect: *rect })?;
+                        let cookie = imp
+                            .display
+                            .connection()
+                            .shm_put_image(
+                                imp.window,
+                                imp.gc,
+                                imp.stride,
+                                surface_height.get(),
+                                src_x,
+                                src_y,
+                                width,
+                                height,
+                                dst_x,
+                                dst_y,
+                                imp.depth,
+                                xproto::ImageFormat::Z_PIXMAP.into(),
+                                false,
+                                segment_id,
+                                0,
                             )
-                            .ok_or(SoftBufferError::DamageOutOfRange { rect: *rect })?;
-                            imp.display
-                                .connection()
-                                .shm_put_image(
-                                    imp.window,
-                                    imp.gc,
-                                    surface_width.get(),
-                                    surface_height.get(),
-                                    src_x,
-                                    src_y,
-                                    width,
-                                    height,
-                                    dst_x,
-                                    dst_y,
-                                    imp.depth,
-                                    xproto::ImageFormat::Z_PIXMAP.into(),
-                                    false,
-                                    segment_id,
-                                    0,
-                                )
-                                .push_err()
-                                .map(|c| c.ignore_error())
-                                .swbuf_err("Failed to draw image to window")
-                        })
-                        .and_then(|()| {
-                            // Send a short request to act as a notification for when the X server is done processing the image.
-                            shm.begin_wait(imp.display.connection())
-                                .swbuf_err("Failed to draw image to window")
-                        })?;
+                            .push_err()
+                            .swbuf_err("Failed to draw image to window")?;
+
+                        // The requests fail the same way, so only the last one is checked.
+                        if let Some(previous) = last.replace(cookie) {
+                            previous.ignore_error();
+                        }
+                    }
+                    if let Some(cookie) = last {
+                        shm.begin_wait(cookie);
+                    }
                 }
             }
         }
@@ -500,10 +1129,7 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle + ?Sized> BufferInterface
     }
 
     fn present(self) -> Result<(), SoftBufferError> {
-        let (width, height) = self
-            .0
-            .size
-            .expect("Must set size of surface before calling `present()`");
+        let (width, height) = self.0.size.ok_or(SoftBufferError::SizeNotSet)?;
         self.present_with_damage(&[Rect {
             x: 0,
             y: 0,
@@ -511,6 +1137,20 @@ impl<D: HasDisplayHandle + ?Sized, W: HasWindowHandle + ?Sized> BufferInterface
             height: height.into(),
         }])
     }
+
+    fn present_and_fetch(self) -> Result<Vec<u32>, PresentAndFetchError> {
+        let imp = self.0;
+        BufferImpl(&mut *imp)
+            .present()
+            .map_err(PresentAndFetchError::Present)?;
+
+        // The X server handles requests in order, so `GetImage` sees the presented image. Wait
+        // for the `shm::PutImage` request anyway, so the buffer is ready to use afterwards.
+        imp.buffer
+            .finish_wait(imp.display.connection())
+            .map_err(PresentAndFetchError::Fetch)?;
+        imp.fetch().map_err(PresentAndFetchError::Fetch)
+    }
 }
 
 impl Buffer {
@@ -524,17 +1164,22 @@ impl Buffer {
         match self {
             Buffer::Shm(ref mut shm) => shm.alloc_segment(conn, total_len(width, height)),
             Buffer::Wire(wire) => {
-                wire.resize(total_len(width, height) / 4, 0);
+                util::resize_buffer(wire, total_len(width, height) / 4);
                 Ok(())
             }
         }
     }
 
+    /// Whether there is an ongoing `shm::PutImage` request.
+    fn is_waiting(&self) -> bool {
+        matches!(self, Buffer::Shm(shm) if shm.done_processing.is_some())
+    }
+
     /// Finish waiting for an ongoing `shm::PutImage` request, if there is one.
     fn finish_wait(&mut self, conn: &impl Connection) -> Result<(), SoftBufferError> {
         if let Buffer::Shm(ref mut shm) = self {
             shm.finish_wait(conn)
-                .swbuf_err("Failed to wait for X11 buffer")?;
+                .window_err("Failed to wait for X11 buffer")?;
         }
 
         Ok(())
@@ -636,6 +1281,43 @@ impl ShmBuffer {
         }
     }
 
+    /// Read the contents of a window into the SHM segment and copy them into `out`.
+    fn fetch(
+        &mut self,
+        conn: &impl Connection,
+        window: xproto::Window,
+        (width, height): (u16, u16),
+        (depth, visual_id): (u8, Visualid),
+        out: &mut Vec<u32>,
+    ) -> Result<(), PushBufferError> {
+        self.alloc_segment(conn, total_len(width, height))?;
+        let seg_id = self.seg.as_ref().unwrap().1;
+
+        // Waiting for the reply means that the X server is done writing to the segment.
+        let reply = conn
+            .shm_get_image(
+                window,
+                0,
+                0,
+                width,
+                height,
+                u32::MAX,
+                xproto::ImageFormat::Z_PIXMAP.into(),
+                seg_id,
+                0,
+            )?
+            .reply()?;
+
+        if reply.depth != depth || reply.visual != visual_id {
+            return Err(PushBufferError::Mismatch);
+        }
+
+        // SAFETY: The X server is done with the segment, and we never hand out references to it.
+        out.clear();
+        out.extend_from_slice(unsafe { self.as_ref() });
+        Ok(())
+    }
+
     /// Associate an SHM segment with the server.
     fn associate(
         &mut self,
@@ -644,8 +1326,12 @@ impl ShmBuffer {
     ) -> Result<(), PushBufferError> {
         // Register the guard.
         let new_id = conn.generate_id()?;
-        conn.shm_attach_fd(new_id, seg.as_fd().try_clone_to_owned().unwrap(), true)?
-            .ignore_error();
+        conn.shm_attach_fd(
+            new_id,
+            seg.as_fd().try_clone_to_owned().unwrap(),
+            self.read_only,
+        )?
+        .ignore_error();
 
         // Take out the old one and detach it.
         if let Some((old_seg, old_id)) = self.seg.replace((seg, new_id)) {
@@ -661,20 +1347,20 @@ impl ShmBuffer {
         Ok(())
     }
 
-    /// Begin waiting for the SHM processing to finish.
-    fn begin_wait(&mut self, c: &impl Connection) -> Result<(), PushBufferError> {
-        let cookie = c.get_input_focus()?.sequence_number();
-        let old_cookie = self.done_processing.replace(cookie);
+    /// Begin waiting for the SHM processing of the last `shm::PutImage` request to finish.
+    fn begin_wait<C: Connection>(&mut self, cookie: VoidCookie<'_, C>) {
+        let old_cookie = self.done_processing.replace(cookie.sequence_number());
         debug_assert!(old_cookie.is_none());
-        Ok(())
+
+        // Dropping the cookie would turn its error into an event, it's checked in `finish_wait`.
+        mem::forget(cookie);
     }
 
     /// Wait for the SHM processing to finish.
     fn finish_wait(&mut self, c: &impl Connection) -> Result<(), PushBufferError> {
         if let Some(done_processing) = self.done_processing.take() {
-            // Cast to a cookie and wait on it.
-            let cookie = Cookie::<_, xproto::GetInputFocusReply>::new(c, done_processing);
-            cookie.reply()?;
+            // Cast to a cookie and check it, which waits for the X server to process it.
+            VoidCookie::new(c, done_processing).check()?;
         }
 
         Ok(())
@@ -787,7 +1473,11 @@ impl<D: ?Sized> Drop for X11DisplayImpl<D> {
 impl<D: ?Sized, W: ?Sized> Drop for X11Impl<D, W> {
     fn drop(&mut self) {
         // If we used SHM, make sure it's detached from the server.
-        if let Buffer::Shm(mut shm) = mem::replace(&mut self.buffer, Buffer::Wire(Vec::new())) {
+        let buffer = match mem::replace(&mut self.buffer, Buffer::Wire(Vec::new())) {
+            Buffer::Shm(shm) => Some(shm),
+            Buffer::Wire(_) => None,
+        };
+        for mut shm in buffer.into_iter().chain(self.fetch_buffer.take()) {
             // If we were in the middle of processing a buffer, wait for it to finish.
             shm.finish_wait(self.display.connection()).ok();
 
@@ -805,6 +1495,9 @@ impl<D: ?Sized, W: ?Sized> Drop for X11Impl<D, W> {
         if let Ok(token) = self.display.connection().free_gc(self.gc) {
             token.ignore_error();
         }
+
+        // Make sure the last presented image and the requests above reach the server.
+        self.display.connection().flush().ok();
     }
 }
 
@@ -876,7 +1569,7 @@ fn is_shm_available(c: &impl Connection) -> bool {
 }
 
 /// Collect all visuals that use softbuffer's pixel format
-fn supported_visuals(c: &impl Connection) -> HashSet<Visualid> {
+fn supported_visuals(c: &impl Connection) -> HashMap<Visualid, u8> {
     // Check that depth 24 uses 32 bits per pixels
     // HACK(notgull): Also support depth 32 for transparent visuals.
     // Otherwise winit users get weird errors.
@@ -887,7 +1580,7 @@ fn supported_visuals(c: &impl Connection) -> HashSet<Visualid> {
         .any(|f| (f.depth == 24 || f.depth == 32) && f.bits_per_pixel == 32)
     {
         tracing::warn!("X11 server does not have a depth 24/32 format with 32 bits per pixel");
-        return HashSet::new();
+        return HashMap::new();
     }
 
     // How does the server represent red, green, blue components of a pixel?
@@ -923,12 +1616,35 @@ fn supported_visuals(c: &impl Connection) -> HashSet<Visualid> {
                             // Colors must be laid out as softbuffer expects
                             expected_masks == (visual.red_mask, visual.green_mask, visual.blue_mask)
                         })
-                        .map(|visual| visual.visual_id)
+                        .map(|visual| (visual.visual_id, depth.depth))
                 })
         })
         .collect()
 }
 
+/// Pick the visual that windows on `screen` should preferably use.
+///
+/// This is the root visual if it's supported, since that's what windows get by default.
+/// Otherwise, fall back to an opaque supported visual on the same screen.
+fn preferred_visual(
+    c: &impl Connection,
+    screen: usize,
+    supported_visuals: &HashMap<Visualid, u8>,
+) -> Option<Visualid> {
+    let screen = c.setup().roots.get(screen)?;
+    if supported_visuals.contains_key(&screen.root_visual) {
+        return Some(screen.root_visual);
+    }
+
+    screen
+        .allowed_depths
+        .iter()
+        .filter(|depth| depth.depth == 24)
+        .flat_map(|depth| &depth.visuals)
+        .map(|visual| visual.visual_id)
+        .find(|visual_id| supported_visuals.contains_key(visual_id))
+}
+
 /// An error that can occur when pushing a buffer to the window.
 #[derive(Debug)]
 enum PushBufferError {
@@ -940,6 +1656,9 @@ enum PushBufferError {
 
     /// A system error occurred while creating the shared memory segment.
     System(io::Error),
+
+    /// The fetched image doesn't match the window's depth or visual.
+    Mismatch,
 }
 
 impl fmt::Display for PushBufferError {
@@ -948,6 +1667,7 @@ impl fmt::Display for PushBufferError {
             Self::X11(e) => write!(f, "X11 error: {}", e),
             Self::XidExhausted => write!(f, "XID space exhausted"),
             Self::System(e) => write!(f, "System error: {}", e),
+            Self::Mismatch => write!(f, "Mismatch between reply and window data"),
         }
     }
 }
@@ -982,6 +1702,17 @@ impl From<io::Error> for PushBufferError {
     }
 }
 
+impl PushBufferError {
+    /// Whether the X server reported that the window doesn't exist anymore.
+    fn is_window_gone(&self) -> bool {
+        matches!(
+            self,
+            Self::X11(ReplyError::X11Error(err))
+                if matches!(err.error_kind, ErrorKind::Window | ErrorKind::Drawable)
+        )
+    }
+}
+
 /// Convenient wrapper to cast errors into PushBufferError.
 trait PushResultExt<T, E> {
     fn push_err(self) -> Result<T, PushBufferError>;
@@ -993,6 +1724,21 @@ impl<T, E: Into<PushBufferError>> PushResultExt<T, E> for Result<T, E> {
     }
 }
 
+/// Like [`SwResultExt`], but reports errors about a missing window as
+/// [`SoftBufferError::WindowClosed`].
+trait WindowResultExt<T> {
+    fn window_err(self, msg: &str) -> Result<T, SoftBufferError>;
+}
+
+impl<T, E: Into<PushBufferError>> WindowResultExt<T> for Result<T, E> {
+    fn window_err(self, msg: &str) -> Result<T, SoftBufferError> {
+        match self.push_err() {
+            Err(err) if err.is_window_gone() => Err(SoftBufferError::WindowClosed),
+            result => result.swbuf_err(msg),
+        }
+    }
+}
+
 /// Get the length that a slice needs to be to hold a buffer of the given dimensions.
 #[inline(always)]
 fn total_len(width: u16, height: u16) -> usize {
@@ -86,12 +86,17 @@ pub enum SoftBufferError {
         height: NonZeroU32,
     },
 
-    /// The provided damage rect is outside of the range supported by the backend.
+    /// The provided damage rect is outside of the buffer, or of the range supported by the backend.
     DamageOutOfRange {
         /// The damage rect that was out of range.
         rect: crate::Rect,
     },
 
+    /// The size of the surface hasn't been set with [`Surface::resize`] yet.
+    ///
+    /// [`Surface::resize`]: crate::Surface::resize
+    SizeNotSet,
+
     /// A platform-specific backend error occurred.
     ///
     /// The first field provides a human-readable description of the error. The second field
@@ -102,6 +107,25 @@ pub enum SoftBufferError {
 
     /// This function is unimplemented on this platform.
     Unimplemented,
+
+    /// Waiting for the display server took longer than the timeout set with
+    /// [`Surface::set_present_timeout`].
+    ///
+    /// [`Surface::set_present_timeout`]: crate::Surface::set_present_timeout
+    Timeout,
+
+    /// The window was destroyed, so nothing can be presented to it anymore.
+    ///
+    /// This is only detected in some cases:
+    ///
+    /// - On X11, when the server no longer knows the window. This is noticed when waiting for the
+    ///   previous present in [`Surface::buffer_mut`], which only happens when presenting with
+    ///   shared memory, or when fetching.
+    /// - On Wayland, only for surfaces that softbuffer created itself. Softbuffer can't tell
+    ///   whether the `wl_surface` of a window that was passed in has been destroyed.
+    ///
+    /// [`Surface::buffer_mut`]: crate::Surface::buffer_mut
+    WindowClosed,
 }
 
 impl fmt::Display for SoftBufferError {
@@ -131,6 +155,7 @@ impl fmt::Display for SoftBufferError {
                 f,
                 "Surface size {width}x{height} out of range for backend.",
             ),
+            Self::SizeNotSet => write!(f, "The size of the surface has not been set."),
             Self::PlatformError(msg, None) => write!(f, "Platform error: {msg:?}"),
             Self::PlatformError(msg, Some(err)) => write!(f, "Platform error: {msg:?}: {err}"),
             Self::DamageOutOfRange { rect } => write!(
@@ -139,6 +164,8 @@ impl fmt::Display for SoftBufferError {
                 rect.width, rect.height, rect.x, rect.y
             ),
             Self::Unimplemented => write!(f, "This function is unimplemented on this platform."),
+            Self::Timeout => write!(f, "Timed out waiting for the display server."),
+            Self::WindowClosed => write!(f, "The window has been closed."),
         }
     }
 }
@@ -159,6 +186,20 @@ impl From<HandleError> for SoftBufferError {
     }
 }
 
+impl SoftBufferError {
+    /// Whether connecting to the display server failed, which may succeed when tried again.
+    pub(crate) fn is_connection_failure(&self) -> bool {
+        #[cfg(x11_platform)]
+        if let Self::PlatformError(_, Some(source)) = self {
+            if source.is::<LibraryError<x11rb::errors::ConnectError>>() {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
 /// Simple unit error type used to bubble up rejected platforms.
 pub(crate) enum InitError<D> {
     /// Failed to initialize.
@@ -16,20 +16,46 @@ mod util;
 
 use std::cell::Cell;
 use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
 use std::num::NonZeroU32;
 use std::ops;
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use error::InitError;
 pub use error::SoftBufferError;
+#[cfg(feature = "image")]
+use error::SwResultExt;
+pub use util::{diff_damage, DirtyTracker};
 
-use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use raw_window_handle::{
+    DisplayHandle, HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle,
+    WindowHandle,
+};
 
+#[cfg(kms_platform)]
+pub use backends::kms::{SurfaceExtKms, VblankInfo};
+#[cfg(wayland_platform)]
+pub use backends::wayland::{ContextExtWayland, SurfaceExtWayland};
 #[cfg(target_arch = "wasm32")]
-pub use backends::web::SurfaceExtWeb;
+pub use backends::web::{CanvasContextOptions, SurfaceExtWeb};
+#[cfg(target_os = "windows")]
+pub use backends::win32::SurfaceExtWin32;
+#[cfg(x11_platform)]
+pub use backends::x11::{ContextExtX11, GcFunction, GcOptions, SurfaceExtX11};
 
 /// An instance of this struct contains the platform-specific data that must be managed in order to
 /// write to a window on that platform.
+///
+/// A `Context<D>` is `Send` and `Sync` if `D` is, so it can be shared between threads that each
+/// create surfaces from it. Cloning it is cheap if cloning `D` is; the connection to the display
+/// server is shared between the clones on X11, Wayland and DRM/KMS.
+///
+/// ## Platform Dependent Behavior
+///
+/// - On the web, nothing is `Send` or `Sync`, since the underlying objects belong to one thread.
+#[derive(Clone)]
 pub struct Context<D> {
     /// The inner static dispatch object.
     context_impl: ContextDispatch<D>,
@@ -56,8 +82,157 @@ impl<D: HasDisplayHandle> Context<D> {
             Err(InitError::Failure(f)) => Err(f),
         }
     }
+
+    /// Like [`Context::new`], but tries again if connecting to the display server fails.
+    ///
+    /// This is useful when the application may start before the display server is ready, e.g.
+    /// in kiosk setups that launch it during boot. The connection is attempted up to `attempts`
+    /// times (at least once), waiting `delay` in between. Other errors, like an unsupported
+    /// display platform, are returned right away.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On X11, this retries when the connection to the X server can't be established.
+    /// - On all other platforms, the connection is owned by the display handle, so this is the
+    ///   same as [`Context::new`].
+    pub fn new_with_retry(
+        display: D,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<Self, SoftBufferError>
+    where
+        D: Clone,
+    {
+        let mut attempts_left = attempts;
+        loop {
+            match Self::new(display.clone()) {
+                Err(err) if attempts_left > 1 && err.is_connection_failure() => {
+                    tracing::warn!("failed to connect to the display server, retrying: {err}");
+                    attempts_left -= 1;
+                    thread::sleep(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// The raw handle of the display that the context was created from.
+    ///
+    /// This can be used to share the display connection with other graphics libraries, without
+    /// keeping a second copy of the display around.
+    pub fn raw_display_handle(&self) -> Result<RawDisplayHandle, SoftBufferError> {
+        Ok(self.context_impl.display().display_handle()?.as_raw())
+    }
+
+    /// The name of the backend that was chosen for the display, e.g. `"X11"` or `"Wayland"`.
+    ///
+    /// This is meant for logging and bug reports; the exact names are not guaranteed to be stable.
+    pub fn backend_name(&self) -> &'static str {
+        self.context_impl.variant_name()
+    }
+}
+
+impl<'a> Context<DisplayHandle<'a>> {
+    /// Creates a new context from a raw display handle, for when the display doesn't come as a
+    /// type that implements [`HasDisplayHandle`], e.g. when it's passed in from C.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be a valid display handle, and the display must stay valid for as long as the
+    /// context and the surfaces created from it are used. See [`DisplayHandle::borrow_raw`].
+    pub unsafe fn from_raw(raw: RawDisplayHandle) -> Result<Self, SoftBufferError> {
+        // SAFETY: Upheld by the caller.
+        Self::new(unsafe { DisplayHandle::borrow_raw(raw) })
+    }
+}
+
+/// A [`Context`] that can hold any display, so that code storing it doesn't have to be generic
+/// over the type of the display.
+///
+/// Create it with [`Context::boxed`]. The surfaces created from it have the same display type,
+/// `Box<dyn HasDisplayHandle + Send + Sync>`.
+pub type DynContext = Context<Box<dyn HasDisplayHandle + Send + Sync>>;
+
+impl Context<Box<dyn HasDisplayHandle + Send + Sync>> {
+    /// Creates a new context for `display`, erasing its type.
+    ///
+    /// ```no_run
+    /// # use raw_window_handle::HasDisplayHandle;
+    /// # use softbuffer::{Context, DynContext, SoftBufferError};
+    /// struct Renderer {
+    ///     context: DynContext,
+    /// }
+    ///
+    /// impl Renderer {
+    ///     fn new(
+    ///         display: impl HasDisplayHandle + Send + Sync + 'static,
+    ///     ) -> Result<Self, SoftBufferError> {
+    ///         Ok(Self {
+    ///             context: Context::boxed(display)?,
+    ///         })
+    ///     }
+    /// }
+    /// ```
+    pub fn boxed(
+        display: impl HasDisplayHandle + Send + Sync + 'static,
+    ) -> Result<Self, SoftBufferError> {
+        Self::new(Box::new(display))
+    }
+}
+
+/// The order in which the channels of a pixel are laid out in memory.
+///
+/// See [`Buffer::as_bytes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ByteOrder {
+    /// Blue, green and red, followed by an unused byte. This is the order on little-endian
+    /// targets.
+    Bgrx,
+    /// An unused byte, followed by red, green and blue. This is the order on big-endian targets.
+    Xrgb,
 }
 
+/// How the alpha channel of a pixel relates to its color channels.
+///
+/// See [`Buffer::blend_rect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// The color channels are independent of the alpha channel.
+    Straight,
+    /// The color channels have already been multiplied by the alpha channel.
+    Premultiplied,
+}
+
+/// Whether [`Surface::resize`] changed the size of the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResizeOutcome {
+    /// The buffer already had the requested size, so its contents and age are unaffected.
+    Unchanged,
+    /// The buffer has a new size, so the whole frame should be redrawn.
+    Resized {
+        /// The previous width and height, or `None` if no size had been set yet.
+        old: Option<(NonZeroU32, NonZeroU32)>,
+        /// The new width and height.
+        new: (NonZeroU32, NonZeroU32),
+    },
+}
+
+/// How long a surface waited for the display server, see [`Surface::last_present_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PresentStats {
+    /// The time that the last [`Surface::buffer_mut`] spent waiting for the display server to be
+    /// done with a previously presented buffer.
+    pub wait: Duration,
+    /// The number of presented buffers that the display server still held on to when the wait
+    /// began.
+    pub in_flight: u8,
+}
+
+/// A function that is called with the damage of every present.
+///
+/// See [`Surface::set_damage_observer`].
+pub type DamageObserver = Box<dyn FnMut(&[Rect]) + Send>;
+
 /// A rectangular region of the buffer coordinate space.
 #[derive(Clone, Copy, Debug)]
 pub struct Rect {
@@ -71,21 +246,204 @@ pub struct Rect {
     pub height: NonZeroU32,
 }
 
+impl Rect {
+    /// Create a rectangle from its top left corner and its size.
+    ///
+    /// Returns `None` if the width or the height is zero.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Option<Self> {
+        Some(Self {
+            x,
+            y,
+            width: NonZeroU32::new(width)?,
+            height: NonZeroU32::new(height)?,
+        })
+    }
+
+    /// Create a rectangle from its top left corner `(x0, y0)` and its bottom right corner
+    /// `(x1, y1)`, which is exclusive.
+    ///
+    /// Returns `None` if the rectangle is empty, i.e. if `x1 <= x0` or `y1 <= y0`.
+    ///
+    /// ```
+    /// # use softbuffer::Rect;
+    /// let rect = Rect::from_bounds(1, 2, 4, 4).unwrap();
+    /// assert_eq!((rect.x, rect.y, rect.width.get(), rect.height.get()), (1, 2, 3, 2));
+    /// assert!(Rect::from_bounds(1, 2, 1, 4).is_none());
+    /// ```
+    pub fn from_bounds(x0: u32, y0: u32, x1: u32, y1: u32) -> Option<Self> {
+        Self::new(x0, y0, x1.checked_sub(x0)?, y1.checked_sub(y0)?)
+    }
+
+    /// The exclusive right and bottom edges, which may lie past `u32::MAX`.
+    fn far_edges(&self) -> (u64, u64) {
+        (
+            u64::from(self.x) + u64::from(self.width.get()),
+            u64::from(self.y) + u64::from(self.height.get()),
+        )
+    }
+
+    /// The number of pixels covered by the rectangle.
+    pub fn area(&self) -> u64 {
+        u64::from(self.width.get()) * u64::from(self.height.get())
+    }
+
+    /// Whether the pixel at `(x, y)` lies within the rectangle.
+    pub fn contains_point(&self, x: u32, y: u32) -> bool {
+        let (right, bottom) = self.far_edges();
+        x >= self.x && y >= self.y && u64::from(x) < right && u64::from(y) < bottom
+    }
+
+    /// The coordinates of the pixels covered by the rectangle, row by row.
+    ///
+    /// Pixels past `u32::MAX` are left out.
+    ///
+    /// ```
+    /// # use softbuffer::Rect;
+    /// let rect = Rect::new(1, 2, 2, 2).unwrap();
+    /// let pixels: Vec<_> = rect.pixels().collect();
+    /// assert_eq!(pixels, [(1, 2), (2, 2), (1, 3), (2, 3)]);
+    /// ```
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        let (x0, y0) = (self.x, self.y);
+        let x1 = x0.saturating_add(self.width.get() - 1);
+        let y1 = y0.saturating_add(self.height.get() - 1);
+        (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| (x, y)))
+    }
+
+    /// The region covered by both rectangles, or `None` if they don't overlap.
+    ///
+    /// ```
+    /// # use softbuffer::Rect;
+    /// let a = Rect::new(0, 0, 4, 4).unwrap();
+    /// let b = Rect::new(2, 3, 4, 4).unwrap();
+    /// let both = a.intersection(&b).unwrap();
+    /// assert_eq!((both.x, both.y, both.width.get(), both.height.get()), (2, 3, 2, 1));
+    /// assert!(a.intersection(&Rect::new(4, 0, 1, 1).unwrap()).is_none());
+    /// ```
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let (right, bottom) = self.far_edges();
+        let (other_right, other_bottom) = other.far_edges();
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        // Both edges are at least `x + 1` and `y + 1` when the rectangles overlap, so the
+        // differences fit in a `u32`.
+        let width = right.min(other_right).checked_sub(u64::from(x))?;
+        let height = bottom.min(other_bottom).checked_sub(u64::from(y))?;
+        Self::new(x, y, width as u32, height as u32)
+    }
+
+    /// The smallest rectangle that covers both rectangles.
+    ///
+    /// The size saturates at `u32::MAX` if the rectangles together span more than that.
+    ///
+    /// ```
+    /// # use softbuffer::Rect;
+    /// let a = Rect::new(0, 0, 2, 2).unwrap();
+    /// let b = Rect::new(3, 1, 2, 2).unwrap();
+    /// let both = a.union(&b);
+    /// assert_eq!((both.x, both.y, both.width.get(), both.height.get()), (0, 0, 5, 3));
+    /// assert_eq!(both.area(), 15);
+    /// ```
+    pub fn union(&self, other: &Rect) -> Rect {
+        let (right, bottom) = self.far_edges();
+        let (other_right, other_bottom) = other.far_edges();
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let width = right.max(other_right) - u64::from(x);
+        let height = bottom.max(other_bottom) - u64::from(y);
+        Rect {
+            x,
+            y,
+            width: NonZeroU32::new(width.min(u32::MAX.into()) as u32).unwrap(),
+            height: NonZeroU32::new(height.min(u32::MAX.into()) as u32).unwrap(),
+        }
+    }
+}
+
 /// A surface for drawing to a window with software buffers.
+///
+/// A `Surface<D, W>` is `Send` if `D` is `Send` and `Sync` and `W` is `Send`, except on the web.
+/// It is never `Sync`, so only one thread can draw to it at a time.
 pub struct Surface<D, W> {
     /// This is boxed so that `Surface` is the same size on every platform.
     surface_impl: Box<SurfaceDispatch<D, W>>,
+    /// Whether damage is clipped to the buffer instead of being rejected.
+    clip_damage: bool,
+    /// The color that fresh buffers are filled with after the surface grows.
+    resize_fill: Option<u32>,
+    /// How many of the next buffers without a previous frame still have to be filled.
+    fills_pending: u8,
+    /// What is known about the presented frames.
+    present_log: PresentLog,
     _marker: PhantomData<Cell<()>>,
 }
 
+/// The bookkeeping of a surface about the frames it presented.
+struct PresentLog {
+    /// The damage of the most recently presented frames, for [`Buffer::stale_rects`].
+    damage_history: DirtyTracker,
+    /// Called with the damage of every present, see [`Surface::set_damage_observer`].
+    damage_observer: Option<DamageObserver>,
+}
+
+impl PresentLog {
+    /// Note that a buffer was presented with `damage`.
+    fn record(&mut self, damage: &[Rect]) {
+        self.damage_history.record(damage);
+        self.observe(damage);
+    }
+
+    /// Note that `damage` was presented without a buffer.
+    fn observe(&mut self, damage: &[Rect]) {
+        if let Some(observer) = &mut self.damage_observer {
+            observer(damage);
+        }
+    }
+}
+
+/// No backend keeps more than two buffers, so after filling two buffers every buffer has either
+/// been filled or holds a frame that was drawn after the resize.
+const BUFFERS_TO_FILL: u8 = 2;
+
+/// How many presented frames to remember the damage of, which covers buffers up to one frame
+/// older than that.
+const DAMAGE_HISTORY_LEN: u8 = 2;
+
 impl<D: HasDisplayHandle, W: HasWindowHandle> Surface<D, W> {
     /// Creates a new surface for the context for the provided window.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On AppKit and UIKit, this must be called on the main thread, since it accesses the view
+    ///   of the window. Afterwards the surface can be sent to another thread, and drawing into
+    ///   and presenting buffers works from any thread, as the layer that is updated is thread
+    ///   safe.
     pub fn new(context: &Context<D>, window: W) -> Result<Self, SoftBufferError> {
-        match SurfaceDispatch::new(window, &context.context_impl) {
-            Ok(surface_dispatch) => Ok(Self {
-                surface_impl: Box::new(surface_dispatch),
-                _marker: PhantomData,
-            }),
+        Self::from_init(context, SurfaceDispatch::new(window, &context.context_impl))
+    }
+
+    /// Wraps a backend surface, with the default settings.
+    pub(crate) fn from_dispatch(surface_dispatch: SurfaceDispatch<D, W>) -> Self {
+        Self {
+            surface_impl: Box::new(surface_dispatch),
+            clip_damage: false,
+            resize_fill: Some(0),
+            fills_pending: 0,
+            present_log: PresentLog {
+                damage_history: DirtyTracker::new(DAMAGE_HISTORY_LEN),
+                damage_observer: None,
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Wraps the result of creating a backend surface, turning unsupported windows into errors.
+    pub(crate) fn from_init(
+        context: &Context<D>,
+        surface: Result<SurfaceDispatch<D, W>, InitError<W>>,
+    ) -> Result<Self, SoftBufferError> {
+        match surface {
+            Ok(surface_dispatch) => Ok(Self::from_dispatch(surface_dispatch)),
             Err(InitError::Unsupported(window)) => {
                 let raw = window.window_handle()?.as_raw();
                 Err(SoftBufferError::UnsupportedWindowPlatform {
@@ -103,42 +461,938 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> Surface<D, W> {
         self.surface_impl.window()
     }
 
-    /// Set the size of the buffer that will be returned by [`Surface::buffer_mut`].
+    /// Rebind the surface to a new window, e.g. after the native window was destroyed and
+    /// recreated.
+    ///
+    /// The size set with [`Surface::resize`] is kept, so the surface can be drawn to right away.
+    /// This is cheaper than creating a new surface, and doesn't require access to the [`Context`].
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Android, this acquires the new `ANativeWindow`, e.g. after `surfaceDestroyed` and
+    ///   `surfaceCreated`, and sets its buffer geometry again.
+    /// - On Wayland, this binds to the new `wl_surface`. The buffers are reallocated, so the age
+    ///   of the next buffer is `0`.
+    /// - On all other platforms, this returns [`SoftBufferError::Unimplemented`], create a new
+    ///   [`Surface`] instead.
+    pub fn replace_window(&mut self, window: W) -> Result<(), SoftBufferError> {
+        match self.surface_impl.replace_window(window) {
+            Ok(()) => Ok(()),
+            Err(InitError::Unsupported(window)) => {
+                let raw = window.window_handle()?.as_raw();
+                Err(SoftBufferError::UnsupportedWindowPlatform {
+                    human_readable_window_platform_name: window_handle_type_name(&raw),
+                    human_readable_display_platform_name: self.surface_impl.variant_name(),
+                    window_handle: raw,
+                })
+            }
+            Err(InitError::Failure(f)) => Err(f),
+        }
+    }
+
+    /// The raw handle of the window that the surface draws to.
+    pub fn raw_window_handle(&self) -> Result<RawWindowHandle, SoftBufferError> {
+        Ok(self.window().window_handle()?.as_raw())
+    }
+
+    /// The name of the backend that the surface uses, e.g. `"X11"` or `"Wayland"`.
+    ///
+    /// This is meant for logging and bug reports; the exact names are not guaranteed to be stable.
+    pub fn backend_name(&self) -> &'static str {
+        self.surface_impl.variant_name()
+    }
+
+    /// Whether the buffer is shared with the display server, so that presenting it doesn't
+    /// require copying it.
+    ///
+    /// Applications can use this to detect a slow path, e.g. to lower their frame rate.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On X11, this is `true` if the XShm extension is available.
+    /// - On Wayland, Win32 and DRM/KMS, this is always `true`.
+    /// - On all other platforms, this is `false`. On Orbital, presenting doesn't copy the buffer
+    ///   when its size matches the window, but that isn't reflected here.
+    pub fn uses_shared_memory(&self) -> bool {
+        self.surface_impl.uses_shared_memory()
+    }
+
+    /// How many buffers the surface cycles through, which is the largest [age](Buffer::age) a
+    /// buffer can have.
+    ///
+    /// To redraw only what changed since a buffer was last presented, the damage of this many
+    /// frames has to be kept, see also [`Buffer::stale_rects`].
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland and DRM/KMS, this is `2`, because they use double buffering, unless Wayland
+    ///   was switched to a single buffer with [`Surface::set_buffer_count`].
+    /// - On AppKit, UIKit and Android, this is `0`, since every buffer is new.
+    /// - On all other platforms, this is `1`.
+    pub fn buffer_count(&self) -> u8 {
+        self.surface_impl.buffer_count()
+    }
+
+    /// Change how many buffers the surface cycles through, see [`Surface::buffer_count`].
+    ///
+    /// Returns [`SoftBufferError::Unimplemented`] if the surface doesn't support `count` buffers.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, `1` presents the same buffer every frame, which halves the shared memory in
+    ///   use. [`Surface::buffer_mut`] then has to wait for the compositor to be done with the
+    ///   buffer before it can be drawn to again, and some compositors hold on to a buffer until
+    ///   the next one is attached, so this should be combined with
+    ///   [`Surface::set_present_timeout`]. `2`, the default, switches back to double buffering.
+    /// - On all other platforms, only the current count is supported.
+    pub fn set_buffer_count(&mut self, count: u8) -> Result<(), SoftBufferError> {
+        self.surface_impl.set_buffer_count(count)
+    }
+
+    /// Whether [`Buffer::present_with_damage`] only updates the damaged parts of the window.
+    ///
+    /// If this is `false`, the whole buffer is presented regardless of the damage, so it's not
+    /// worth computing fine-grained damage. The contents outside of the damage still have to be
+    /// up to date either way.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this is `true` if the compositor supports `wl_surface.damage_buffer`.
+    /// - On Win32, this is `false` while [`Surface::set_alpha_blending`] is enabled.
+    /// - On X11, Android, Web and headless surfaces, this is always `true`.
+    /// - On DRM/KMS, this is `false`, since the whole buffer is flipped to the screen. The damage
+    ///   is only forwarded to the driver with `DIRTYFB` as a hint.
+    /// - On all other platforms, this is `false`. On Orbital, damage is used when the buffer
+    ///   size doesn't match the window, but that isn't reflected here.
+    pub fn supports_partial_present(&self) -> bool {
+        self.surface_impl.supports_partial_present()
+    }
+
+    /// Clip the damage passed to [`Buffer::present_with_damage`] to the bounds of the buffer,
+    /// instead of returning [`SoftBufferError::DamageOutOfRange`].
+    ///
+    /// Each rectangle is intersected with the buffer, and rectangles that lie completely outside
+    /// of it are dropped. This helps when damage is computed from a window size that is already
+    /// out of date. Clipping is disabled by default.
+    pub fn set_damage_clip(&mut self, clip: bool) {
+        self.clip_damage = clip;
+    }
+
+    /// Set the color that buffers are filled with after the surface grows, or `None` to leave
+    /// them as they are.
+    ///
+    /// Depending on the platform, a buffer that doesn't contain a previous frame may hold
+    /// leftovers from before the resize instead of being zeroed. When a resize makes the surface
+    /// larger in either dimension, the next buffers returned by [`Surface::buffer_mut`] whose
+    /// [`Buffer::age`] is `0` are filled with this color, so that regions the application
+    /// doesn't redraw don't show garbage. Applications that always redraw the whole buffer can
+    /// turn this off to save the work.
+    ///
+    /// The default is `Some(0)`, i.e. black.
+    pub fn set_resize_fill(&mut self, color: Option<u32>) {
+        self.resize_fill = color;
+    }
+
+    /// Set a function that is called with the damage of every present, or remove it with `None`.
+    ///
+    /// This is purely for observing what is presented, e.g. to visualize damage in a debug overlay
+    /// or to send it to a tracing tool, and doesn't change how anything is presented. The damage
+    /// is the one that was presented after clipping with [`Surface::set_damage_clip`], which is
+    /// the whole buffer for [`Buffer::present`].
+    ///
+    /// ```no_run
+    /// # use softbuffer::Surface;
+    /// # use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+    /// fn trace_damage<D: HasDisplayHandle, W: HasWindowHandle>(surface: &mut Surface<D, W>) {
+    ///     surface.set_damage_observer(Some(Box::new(|damage| {
+    ///         let area: u64 = damage.iter().map(|rect| rect.area()).sum();
+    ///         eprintln!("presented {} rects covering {area} pixels", damage.len());
+    ///     })));
+    /// }
+    /// ```
+    pub fn set_damage_observer(&mut self, observer: Option<DamageObserver>) {
+        self.present_log.damage_observer = observer;
+    }
+
+    /// Remember to fill the next buffers, if the surface grew from `old` to the new size.
+    fn note_resize(&mut self, old: Option<(NonZeroU32, NonZeroU32)>) {
+        let new = self.surface_impl.size();
+        let grew = match (old, new) {
+            (Some((old_width, old_height)), Some((width, height))) => {
+                width > old_width || height > old_height
+            }
+            (None, new) => new.is_some(),
+            (_, None) => false,
+        };
+        if grew {
+            self.fills_pending = BUFFERS_TO_FILL;
+        }
+    }
+
+    /// Set the size of the buffer that will be returned by [`Surface::buffer_mut`].
+    ///
+    /// If the size of the buffer does not match the size of the window, the buffer is drawn
+    /// in the upper-left corner of the window. It is recommended in most production use cases
+    /// to have the buffer fill the entire window. Use your windowing library to find the size
+    /// of the window.
+    ///
+    /// Resizing to the current size is cheap and keeps the buffers as they are, which happens
+    /// often since windowing libraries may report resize events without a change in size. The
+    /// returned [`ResizeOutcome`] tells whether the size changed, so that a full redraw can be
+    /// skipped otherwise.
+    pub fn resize(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Result<ResizeOutcome, SoftBufferError> {
+        let old = self.surface_impl.size();
+        self.surface_impl.resize(width, height)?;
+        self.note_resize(old);
+
+        let new = (width, height);
+        if old == Some(new) {
+            Ok(ResizeOutcome::Unchanged)
+        } else {
+            Ok(ResizeOutcome::Resized { old, new })
+        }
+    }
+
+    /// The largest width and height that the backend supports for the buffer.
+    ///
+    /// [`Surface::resize`] returns [`SoftBufferError::SizeOutOfRange`] for larger sizes. Note
+    /// that sizes within these limits may still fail to resize, e.g. when running out of memory.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On X11, this is `u16::MAX` in both dimensions.
+    /// - On Wayland, Win32 and Android, this is `i32::MAX` in both dimensions.
+    /// - On DRM/KMS, this is the largest framebuffer size that the device reports.
+    /// - On all other platforms, this is `u32::MAX` in both dimensions.
+    pub fn maximum_size(&self) -> (NonZeroU32, NonZeroU32) {
+        self.surface_impl.maximum_size()
+    }
+
+    /// Set the size of the buffer, with each row of the buffer being `stride` pixels long.
+    ///
+    /// The pixels after the first `width` pixels of each row are padding, which is never shown
+    /// in the window. This allows aligning each row, e.g. for rendering with SIMD. See
+    /// [`Buffer::stride`].
+    ///
+    /// # Platform dependent behavior
+    ///
+    /// Supported on:
+    /// - X, when XShm is available
+    /// - Headless
+    ///
+    /// Elsewhere, [`SoftBufferError::Unimplemented`] is returned unless `stride` equals `width`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SoftBufferError::SizeOutOfRange`] if `stride` is less than `width`.
+    pub fn resize_with_stride(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+        stride: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        if stride < width {
+            return Err(SoftBufferError::SizeOutOfRange { width, height });
+        }
+        let old = self.surface_impl.size();
+        self.surface_impl
+            .resize_with_stride(width, height, stride)?;
+        self.note_resize(old);
+        Ok(())
+    }
+
+    /// The size of the buffer that will be returned by [`Surface::buffer_mut`], as last set with
+    /// [`Surface::resize`].
+    ///
+    /// Returns `None` if the size hasn't been set yet.
+    pub fn size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        self.surface_impl.size()
+    }
+
+    /// Set the size of the buffer, keeping its current contents where the old and new sizes
+    /// overlap.
+    ///
+    /// This is like [`Surface::resize`], except that the top-left region of the last presented
+    /// buffer that still fits is copied into the new buffer, and newly exposed pixels are set to
+    /// zero. In that case [`Buffer::age`] returns `1` afterwards, so only the newly exposed region
+    /// has to be redrawn.
+    ///
+    /// The region is the top-left of the window as it is shown, so with
+    /// [`Surface::set_vertical_flip`] the last rows of the buffer are kept.
+    ///
+    /// The windowing system may still clear the window itself on resize, so the whole buffer
+    /// should be presented after resizing, e.g. with [`Buffer::present`].
+    ///
+    /// # Platform dependent behavior
+    ///
+    /// Supported on:
+    /// - X
+    /// - Win32
+    /// - Web
+    /// - Headless
+    ///
+    /// Otherwise this is equivalent to [`Self::resize`].
+    pub fn resize_preserving(
+        &mut self,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Result<(), SoftBufferError> {
+        // Buffers that were preserved have an age of 1, so they aren't filled.
+        let old = self.surface_impl.size();
+        self.surface_impl.resize_preserving(width, height)?;
+        self.note_resize(old);
+        Ok(())
+    }
+
+    /// Copies the window contents into a buffer.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On X11, the window must be visible.
+    /// - On Redox and Wayland, this function is unimplemented.
+    /// - On Web, this will fail if the content was supplied by
+    ///   a different origin depending on the sites CORS rules.
+    /// - On AppKit, UIKit, DRM/KMS and headless surfaces, this returns the contents as of the
+    ///   last present, or black if nothing was presented at the current size.
+    pub fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
+        self.surface_impl.fetch()
+    }
+
+    /// Copies the window contents into `out`, reusing its allocation.
+    ///
+    /// `out` is cleared and filled with the same pixels that [`Surface::fetch`] would return.
+    /// Passing the same `Vec` on every call avoids allocating a new one for each frame, e.g. when
+    /// capturing the window continuously. The platform dependent behavior is the same as for
+    /// [`Surface::fetch`].
+    pub fn fetch_into(&mut self, out: &mut Vec<u32>) -> Result<(), SoftBufferError> {
+        self.surface_impl.fetch_into(out)
+    }
+
+    /// Writes the window contents to a PNG file at `path`, e.g. to attach what softbuffer
+    /// presented to a bug report.
+    ///
+    /// The contents are captured with [`Surface::fetch`], which has the same platform dependent
+    /// behavior.
+    #[cfg(feature = "image")]
+    pub fn dump_to_png(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), SoftBufferError> {
+        let (width, height) = self.size().ok_or(SoftBufferError::SizeNotSet)?;
+        let pixels = self.fetch()?;
+        if pixels.len() as u64 != u64::from(width.get()) * u64::from(height.get()) {
+            return Err(SoftBufferError::PlatformError(
+                Some(format!(
+                    "Fetched {} pixels for a {width}x{height} surface",
+                    pixels.len()
+                )),
+                None,
+            ));
+        }
+
+        let rgb = pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                [r, g, b]
+            })
+            .collect();
+        // The length was checked above.
+        let image = image::RgbImage::from_raw(width.get(), height.get(), rgb).unwrap();
+        image
+            .save_with_format(path, image::ImageFormat::Png)
+            .swbuf_err("Failed to write PNG")
+    }
+
+    /// Copies a part of the window contents into a buffer.
+    ///
+    /// The returned buffer has `rect.width * rect.height` pixels, without any padding. This is
+    /// much cheaper than [`Surface::fetch`] when only a small region is needed, e.g. for a color
+    /// picker.
+    ///
+    /// Returns [`SoftBufferError::DamageOutOfRange`] if the rectangle doesn't lie within the
+    /// surface.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On X11, the window must be visible.
+    /// - On Web, this will fail if the content was supplied by
+    ///   a different origin depending on the sites CORS rules.
+    /// - On headless surfaces, this returns the contents as of the last present.
+    /// - On AppKit, UIKit, Redox, Wayland, Android and DRM/KMS, this function is unimplemented.
+    pub fn fetch_rect(&mut self, rect: Rect) -> Result<Vec<u32>, SoftBufferError> {
+        let (width, height) = self
+            .surface_impl
+            .size()
+            .ok_or(SoftBufferError::SizeNotSet)?;
+        util::validate_damage(&[rect], width, height)?;
+        self.surface_impl.fetch_rect(rect)
+    }
+
+    /// Block until the last presented buffer has been shown on screen.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On DRM/KMS, this waits for the page flip requested by the last present to complete.
+    ///   This reads all pending events from the DRM device, so page flip and vblank events meant
+    ///   for other users of the device are discarded.
+    /// - On all other platforms, this returns immediately.
+    pub fn wait_for_vblank(&mut self) -> Result<(), SoftBufferError> {
+        self.surface_impl.wait_for_vblank()
+    }
+
+    /// Send any requests that are still buffered to the display server, so that the last
+    /// presented frame is shown even if the application exits right away.
+    ///
+    /// Dropping the surface does this as well, but ignores any errors.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this flushes the event queue of the connection.
+    /// - On X11, this flushes the connection.
+    /// - On all other platforms, presenting doesn't buffer anything and this does nothing.
+    pub fn flush(&mut self) -> Result<(), SoftBufferError> {
+        self.surface_impl.flush()
+    }
+
+    /// Blend the presented buffer with whatever is behind the window, for overlays and other
+    /// translucent windows.
+    ///
+    /// While enabled, the upper 8 bits of each pixel are its alpha value, and the color channels
+    /// must be premultiplied by it. Disabled by default, in which case the upper 8 bits are
+    /// ignored.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Win32, this makes the window a layered window (`WS_EX_LAYERED`) and presents with
+    ///   `UpdateLayeredWindow`, which always updates the whole window regardless of the damage.
+    ///   Disabling it removes the layered style again, and presents with `BitBlt`.
+    /// - On all other platforms, this returns [`SoftBufferError::Unimplemented`].
+    pub fn set_alpha_blending(&mut self, enabled: bool) -> Result<(), SoftBufferError> {
+        self.surface_impl.set_alpha_blending(enabled)
+    }
+
+    /// Tell the compositor which parts of the surface are fully opaque.
+    ///
+    /// Compositors can use this to skip drawing whatever is behind those parts. An empty slice
+    /// marks the whole surface as possibly transparent. The region is given in surface
+    /// coordinates, and takes effect with the next presented buffer.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this sets the opaque region of the `wl_surface`.
+    /// - On X11, this sets the `_NET_WM_OPAQUE_REGION` property of the window.
+    /// - On all other platforms, this returns [`SoftBufferError::Unimplemented`].
+    pub fn set_opaque_region(&mut self, rects: &[Rect]) -> Result<(), SoftBufferError> {
+        self.surface_impl.set_opaque_region(rects)
+    }
+
+    /// Set the scale of the buffer relative to the surface, for rendering at the physical
+    /// resolution of HiDPI displays.
+    ///
+    /// With a scale of `2`, a buffer of 200x200 pixels covers a surface that is 100x100 logical
+    /// pixels in size. The size of the buffer should be a multiple of the scale. The new scale
+    /// takes effect with the next presented buffer, which is damaged in its entirety.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this calls `wl_surface.set_buffer_scale`. If the compositor doesn't support
+    ///   it (`wl_surface` version 2 or lower), this returns [`SoftBufferError::Unimplemented`]
+    ///   for any scale other than `1`.
+    /// - On all other platforms, the buffer always covers the window in physical pixels, and
+    ///   this returns [`SoftBufferError::Unimplemented`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SoftBufferError::PlatformError`] if `scale` is less than `1`.
+    pub fn set_buffer_scale(&mut self, scale: i32) -> Result<(), SoftBufferError> {
+        if scale < 1 {
+            return Err(SoftBufferError::PlatformError(
+                Some(format!("Buffer scale must be at least 1, got {scale}")),
+                None,
+            ));
+        }
+        self.surface_impl.set_buffer_scale(scale)
+    }
+
+    /// Pin the scale of the presented contents, so that each pixel of the buffer maps to exactly
+    /// `scale` pixels of the display per dimension, regardless of the scale factor of the window.
+    ///
+    /// With a scale of `1.0`, the buffer is shown 1:1 in physical pixels, without being rescaled
+    /// by the OS. This gives deterministic output for pixel art and testing. Passing `None`
+    /// restores the automatic behavior.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On AppKit and UIKit, this sets the `contentsScale` of the layer. While a scale is set,
+    ///   it is no longer kept in sync with the scale factor of the window.
+    /// - On Wayland, this is the same as [`Surface::set_buffer_scale`], with `None` meaning a
+    ///   scale of `1`. Scales that aren't whole numbers return [`SoftBufferError::Unimplemented`].
+    /// - On all other platforms, this returns [`SoftBufferError::Unimplemented`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SoftBufferError::PlatformError`] if `scale` isn't a finite, positive number.
+    pub fn set_content_scale(&mut self, scale: Option<f64>) -> Result<(), SoftBufferError> {
+        if let Some(scale) = scale {
+            if !(scale.is_finite() && scale > 0.0) {
+                return Err(SoftBufferError::PlatformError(
+                    Some(format!(
+                        "Content scale must be finite and positive, got {scale}"
+                    )),
+                    None,
+                ));
+            }
+        }
+        self.surface_impl.set_content_scale(scale)
+    }
+
+    /// Show the rows of the buffer bottom to top, for presenting bottom-up images without
+    /// flipping them first.
+    ///
+    /// While this is enabled, the first row of the buffer is shown at the bottom of the window.
+    /// The coordinates of the buffer, including the damage passed to
+    /// [`Buffer::present_with_damage`], still count rows in the order they are stored. Changing
+    /// this may reallocate the buffer, so the next buffer can have an age of 0.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Win32, this creates the buffer as a bottom-up bitmap, so presenting costs the same
+    ///   either way.
+    /// - On headless surfaces, the rows are reversed while presenting.
+    /// - On all other platforms, this returns [`SoftBufferError::Unimplemented`].
+    pub fn set_vertical_flip(&mut self, flip: bool) -> Result<(), SoftBufferError> {
+        self.surface_impl.set_vertical_flip(flip)
+    }
+
+    /// Limit how long [`Surface::buffer_mut`] may block waiting for the display server to
+    /// release a buffer.
+    ///
+    /// If the timeout passes, [`Surface::buffer_mut`] returns [`SoftBufferError::Timeout`]
+    /// instead of blocking forever, e.g. when the compositor is frozen. Calling it again
+    /// continues waiting. `None`, the default, waits without a limit.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this limits waiting for the compositor to release the back buffer.
+    /// - On all other platforms, this has no effect.
+    pub fn set_present_timeout(&mut self, timeout: Option<Duration>) {
+        self.surface_impl.set_present_timeout(timeout)
+    }
+
+    /// Start or stop measuring how long presenting frames waits for the display server.
+    ///
+    /// This is off by default, to not spend time on the measurements. See
+    /// [`Surface::last_present_stats`].
+    pub fn set_present_stats(&mut self, enabled: bool) {
+        self.surface_impl.set_present_stats(enabled)
+    }
+
+    /// How long the last [`Surface::buffer_mut`] waited for the display server, which helps
+    /// telling whether a slow frame is spent drawing or waiting.
+    ///
+    /// Returns `None` if measuring was not turned on with [`Surface::set_present_stats`], or no
+    /// buffer was requested since.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this is the time spent waiting for the compositor to release the back
+    ///   buffer.
+    /// - On X11, this is the time spent waiting for the X server to finish the last
+    ///   `shm::PutImage` request.
+    /// - On all other platforms, this always returns `None`.
+    pub fn last_present_stats(&self) -> Option<PresentStats> {
+        self.surface_impl.last_present_stats()
+    }
+
+    /// Present a frame that was rendered into memory owned by the caller.
+    ///
+    /// `pixels` holds [`Surface::size`] tightly packed pixels, in the format described in the
+    /// [`Buffer`] documentation. They are copied into the next buffer, which is then presented
+    /// with the given damage like with [`Buffer::present_with_damage`]. This allows showing one
+    /// frame in several windows, e.g. a main window and a preview, while rendering it only once:
+    ///
+    /// ```no_run
+    /// # use softbuffer::{Rect, SoftBufferError, Surface};
+    /// # use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+    /// fn mirror<D: HasDisplayHandle, W: HasWindowHandle>(
+    ///     surfaces: &mut [Surface<D, W>],
+    ///     frame: &[u32],
+    ///     damage: &[Rect],
+    /// ) -> Result<(), SoftBufferError> {
+    ///     for surface in surfaces {
+    ///         surface.present_shared(frame, damage)?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `pixels` doesn't match the size of the surface.
+    pub fn present_shared(
+        &mut self,
+        pixels: &[u32],
+        damage: &[Rect],
+    ) -> Result<(), SoftBufferError> {
+        let mut buffer = self.buffer_mut()?;
+        let width = buffer.width().get() as usize;
+        assert_eq!(
+            pixels.len() as u64,
+            width as u64 * u64::from(buffer.height().get()),
+            "`pixels` must hold exactly the pixels of the surface"
+        );
+
+        // Copy the whole frame, the buffer may hold an older frame outside of the damage.
+        for (y, src) in pixels.chunks_exact(width).enumerate() {
+            buffer.row_mut(y as u32).copy_from_slice(src);
+        }
+        buffer.present_with_damage(damage)
+    }
+
+    /// Present a frame from memory owned by the caller, without going through a [`Buffer`]
+    /// where the backend allows it.
+    ///
+    /// `pixels` holds [`Surface::size`] tightly packed pixels, in the format described in the
+    /// [`Buffer`] documentation. `damage` limits which parts of the window are updated like with
+    /// [`Buffer::present_with_damage`], `None` updates all of it. This suits applications that
+    /// keep their own framebuffer, and would otherwise copy it into a buffer every frame.
+    ///
+    /// Where the frame is sent directly, the buffers returned by [`Surface::buffer_mut`] don't
+    /// change, so their [age](Buffer::age) doesn't account for this frame.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On X11, this sends the damaged regions with `PutImage`, straight from `pixels` if they
+    ///   span whole rows.
+    /// - On Win32, this copies each damaged region into a temporary bitmap, and draws it with
+    ///   `BitBlt`.
+    /// - On headless surfaces, this updates the contents returned by [`Surface::fetch`].
+    /// - On all other platforms, this copies the frame into the next buffer and presents it,
+    ///   like [`Surface::present_shared`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SoftBufferError::SizeNotSet`] if the size wasn't set, and
+    /// [`SoftBufferError::DamageOutOfRange`] if any of the rectangles doesn't lie within the
+    /// surface, unless clipping was enabled with [`Surface::set_damage_clip`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `pixels` doesn't match the size of the surface.
+    pub fn present_pixels(
+        &mut self,
+        pixels: &[u32],
+        damage: Option<&[Rect]>,
+    ) -> Result<(), SoftBufferError> {
+        let (width, height) = self
+            .surface_impl
+            .size()
+            .ok_or(SoftBufferError::SizeNotSet)?;
+        assert_eq!(
+            pixels.len() as u64,
+            u64::from(width.get()) * u64::from(height.get()),
+            "`pixels` must hold exactly the pixels of the surface"
+        );
+
+        let damage = match damage {
+            Some(damage) if self.clip_damage => util::clip_damage(damage, width, height),
+            Some(damage) => {
+                util::validate_damage(damage, width, height)?;
+                damage.to_vec()
+            }
+            None => vec![Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }],
+        };
+
+        let width = width.get() as usize;
+        let mut scratch = Vec::new();
+        for (i, rect) in damage.iter().enumerate() {
+            let (x, y) = (rect.x as usize, rect.y as usize);
+            let (rect_width, rect_height) = (rect.width.get() as usize, rect.height.get() as usize);
+            let tile = if rect_width == width {
+                // Whole rows are already laid out like the tile.
+                &pixels[y * width..(y + rect_height) * width]
+            } else {
+                scratch.clear();
+                for row in pixels[y * width..].chunks(width).take(rect_height) {
+                    scratch.extend_from_slice(&row[x..x + rect_width]);
+                }
+                &scratch[..]
+            };
+
+            match self.surface_impl.present_tile(tile, *rect) {
+                Err(SoftBufferError::Unimplemented) if i == 0 => {
+                    return self.present_shared(pixels, &damage);
+                }
+                result => result?,
+            }
+        }
+
+        self.present_log.observe(&damage);
+        Ok(())
+    }
+
+    /// Present `pixels` in the `tile` region of the window, leaving the rest of the window
+    /// untouched.
+    ///
+    /// `pixels` holds `tile.width` by `tile.height` tightly packed pixels, in the format
+    /// described in the [`Buffer`] documentation. This lets tiled renderers present each tile as
+    /// it is finished, without allocating a buffer the size of the whole surface. The tile is not
+    /// written into the buffer returned by [`Surface::buffer_mut`], so presenting that buffer
+    /// afterwards overwrites it.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On X11, this sends the tile with `PutImage`.
+    /// - On Win32, this copies the tile into a temporary bitmap, and draws it with `BitBlt`.
+    /// - On headless surfaces, this updates the contents returned by [`Surface::fetch`]. The size
+    ///   must be set first, and the tile must lie within it.
+    /// - On all other platforms, this returns [`SoftBufferError::Unimplemented`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `pixels` is not `tile.width * tile.height`.
+    pub fn present_tile(&mut self, pixels: &[u32], tile: Rect) -> Result<(), SoftBufferError> {
+        assert_eq!(
+            pixels.len() as u64,
+            tile.area(),
+            "`pixels` must hold exactly the pixels of the tile"
+        );
+        self.surface_impl.present_tile(pixels, tile)?;
+        self.present_log.observe(&[tile]);
+        Ok(())
+    }
+
+    /// Return a [`Buffer`] that the next frame should be rendered into. The size must
+    /// be set with [`Surface::resize`] first, otherwise [`SoftBufferError::SizeNotSet`] is
+    /// returned. The initial contents of the buffer may be zeroed, or may contain a previous
+    /// frame. Call [`Buffer::age`] to determine this.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On DRM/KMS, this doesn't wait for the page flip of the previous frame to happen. Either call
+    ///   [`Surface::wait_for_vblank`] first, or wait for the page flip yourself before sending
+    ///   another frame.
+    pub fn buffer_mut(&mut self) -> Result<Buffer<'_, D, W>, SoftBufferError> {
+        let mut buffer = Buffer {
+            buffer_impl: self.surface_impl.buffer_mut()?,
+            clip_damage: self.clip_damage,
+            present_log: &mut self.present_log,
+            _marker: PhantomData,
+        };
+
+        if self.fills_pending > 0 {
+            if buffer.age() == 0 {
+                if let Some(color) = self.resize_fill {
+                    buffer.fill(color);
+                }
+                self.fills_pending -= 1;
+            } else {
+                // The buffers hold frames drawn after the resize.
+                self.fills_pending = 0;
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Like [`Surface::buffer_mut`], but returns `Ok(None)` instead of blocking if no buffer is
+    /// available yet.
+    ///
+    /// This is useful for latency-sensitive applications that would rather skip drawing a frame
+    /// and do something else than wait for the display server.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this dispatches the events that already arrived once, and returns `None` if
+    ///   the compositor still hasn't released the back buffer.
+    /// - On X11, the previous present is finished with a round trip to the server that can't be
+    ///   checked without blocking, so this waits for it like [`Surface::buffer_mut`].
+    /// - On all other platforms, this is the same as [`Surface::buffer_mut`], and always returns
+    ///   `Some`.
+    pub fn try_buffer_mut(&mut self) -> Result<Option<Buffer<'_, D, W>>, SoftBufferError> {
+        if !self.surface_impl.buffer_ready()? {
+            return Ok(None);
+        }
+        self.buffer_mut().map(Some)
+    }
+
+    /// Begin a new [`Frame`], which holds the next buffer together with the damage accumulated
+    /// while drawing it.
+    ///
+    /// This is useful when a frame is drawn in multiple passes that each damage part of the
+    /// buffer. The frame is presented with the accumulated damage when [`Frame::present`] is
+    /// called, or when it is dropped.
+    ///
+    /// ```no_run
+    /// # use softbuffer::{Rect, SoftBufferError, Surface};
+    /// # use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+    /// fn draw<D: HasDisplayHandle, W: HasWindowHandle>(
+    ///     surface: &mut Surface<D, W>,
+    ///     dirty: &[Rect],
+    /// ) -> Result<(), SoftBufferError> {
+    ///     let mut frame = surface.begin_frame()?;
+    ///     for rect in dirty {
+    ///         let stride = frame.buffer_mut().stride().get();
+    ///         for y in rect.y..rect.y + rect.height.get() {
+    ///             let start = (y * stride + rect.x) as usize;
+    ///             frame.buffer_mut()[start..start + rect.width.get() as usize].fill(0x00ff0000);
+    ///         }
+    ///         frame.add_damage(*rect);
+    ///     }
+    ///     frame.present()
+    /// }
+    /// ```
+    pub fn begin_frame(&mut self) -> Result<Frame<'_, D, W>, SoftBufferError> {
+        Ok(Frame {
+            buffer: Some(self.buffer_mut()?),
+            damage: Vec::new(),
+        })
+    }
+
+    /// Like [`Surface::buffer_mut`], but the contents of the buffer may be uninitialized.
+    ///
+    /// This saves clearing the buffer on backends that allocate a new one for every frame, which
+    /// is wasted work if every pixel gets overwritten anyway. Write every pixel of the returned
+    /// [`UninitBuffer`], then call [`UninitBuffer::assume_init`] to get a [`Buffer`] that can be
+    /// presented.
+    ///
+    /// ```no_run
+    /// # use softbuffer::{SoftBufferError, Surface};
+    /// # use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+    /// # use std::mem::MaybeUninit;
+    /// fn draw<D: HasDisplayHandle, W: HasWindowHandle>(
+    ///     surface: &mut Surface<D, W>,
+    /// ) -> Result<(), SoftBufferError> {
+    ///     let mut buffer = surface.buffer_mut_uninit()?;
+    ///     buffer.fill(MaybeUninit::new(0x00ff0000));
+    ///     // SAFETY: Every pixel was written above.
+    ///     let buffer = unsafe { buffer.assume_init() };
+    ///     buffer.present()
+    /// }
+    /// ```
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On AppKit and UIKit, the buffer is left uninitialized.
+    /// - On all other platforms, this is the same as [`Surface::buffer_mut`], and the buffer
+    ///   may contain a previous frame.
+    pub fn buffer_mut_uninit(&mut self) -> Result<UninitBuffer<'_, D, W>, SoftBufferError> {
+        Ok(UninitBuffer {
+            buffer: Buffer {
+                // SAFETY: `UninitBuffer` only gives out the pixels as `MaybeUninit` until
+                // `assume_init` is called, whose caller has to make sure that every pixel
+                // has been written.
+                buffer_impl: unsafe { self.surface_impl.buffer_mut_uninit()? },
+                clip_damage: self.clip_damage,
+                present_log: &mut self.present_log,
+                _marker: PhantomData,
+            },
+        })
+    }
+}
+
+impl<'w, D: HasDisplayHandle, W: HasWindowHandle + ?Sized> Surface<D, &'w W> {
+    /// Creates a new surface for the context that borrows the window instead of owning it.
     ///
-    /// If the size of the buffer does not match the size of the window, the buffer is drawn
-    /// in the upper-left corner of the window. It is recommended in most production use cases
-    /// to have the buffer fill the entire window. Use your windowing library to find the size
-    /// of the window.
-    pub fn resize(&mut self, width: NonZeroU32, height: NonZeroU32) -> Result<(), SoftBufferError> {
-        self.surface_impl.resize(width, height)
+    /// This is the same as [`Surface::new`] with a reference to the window, and is useful when
+    /// the window is owned elsewhere and outlives the surface, so it doesn't need to be wrapped
+    /// in an `Arc`.
+    ///
+    /// ```no_run
+    /// # use softbuffer::{Context, SoftBufferError, Surface};
+    /// # use std::num::NonZeroU32;
+    /// # use winit::window::Window;
+    /// fn draw(window: &Window) -> Result<(), SoftBufferError> {
+    ///     let context = Context::new(window)?;
+    ///     let mut surface = Surface::new_borrowed(&context, window)?;
+    ///
+    ///     let size = window.inner_size();
+    ///     if let (Some(width), Some(height)) =
+    ///         (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+    ///     {
+    ///         surface.resize(width, height)?;
+    ///         let mut buffer = surface.buffer_mut()?;
+    ///         buffer.fill(0x00ff0000);
+    ///         buffer.present()?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn new_borrowed(context: &Context<D>, window: &'w W) -> Result<Self, SoftBufferError> {
+        Self::new(context, window)
     }
+}
 
-    /// Copies the window contents into a buffer.
+impl<'a, D: HasDisplayHandle> Surface<D, WindowHandle<'a>> {
+    /// Creates a new surface from a raw window handle, for when the window doesn't come as a
+    /// type that implements [`HasWindowHandle`], e.g. when it's passed in from C.
     ///
-    /// ## Platform Dependent Behavior
+    /// ```no_run
+    /// # use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+    /// # use softbuffer::{Context, SoftBufferError, Surface};
+    /// unsafe fn attach(
+    ///     display: RawDisplayHandle,
+    ///     window: RawWindowHandle,
+    /// ) -> Result<(), SoftBufferError> {
+    ///     let context = unsafe { Context::from_raw(display)? };
+    ///     let surface = unsafe { Surface::from_raw(&context, window)? };
+    ///     // ...
+    /// #   drop(surface);
+    ///     Ok(())
+    /// }
+    /// ```
     ///
-    /// - On X11, the window must be visible.
-    /// - On AppKit, UIKit, Redox and Wayland, this function is unimplemented.
-    /// - On Web, this will fail if the content was supplied by
-    ///   a different origin depending on the sites CORS rules.
-    pub fn fetch(&mut self) -> Result<Vec<u32>, SoftBufferError> {
-        self.surface_impl.fetch()
+    /// # Safety
+    ///
+    /// `raw` must be a valid window handle that belongs to the display of the context, and the
+    /// window must stay valid for as long as the surface is used. See
+    /// [`WindowHandle::borrow_raw`].
+    pub unsafe fn from_raw(
+        context: &Context<D>,
+        raw: RawWindowHandle,
+    ) -> Result<Self, SoftBufferError> {
+        // SAFETY: Upheld by the caller.
+        Self::new(context, unsafe { WindowHandle::borrow_raw(raw) })
     }
+}
 
-    /// Return a [`Buffer`] that the next frame should be rendered into. The size must
-    /// be set with [`Surface::resize`] first. The initial contents of the buffer may be zeroed, or
-    /// may contain a previous frame. Call [`Buffer::age`] to determine this.
+#[cfg(feature = "headless")]
+impl Surface<NoDisplayHandle, NoWindowHandle> {
+    /// Creates a new surface of the given size that isn't attached to any window.
     ///
-    /// ## Platform Dependent Behavior
+    /// Presenting the buffer copies it (or the damaged regions of it) into memory, where it can
+    /// be read back with [`Surface::fetch`]. This makes it possible to test rendering code
+    /// without a windowing system.
     ///
-    /// - On DRM/KMS, there is no reliable and sound way to wait for the page flip to happen from within
-    ///   `softbuffer`. Therefore it is the responsibility of the user to wait for the page flip before
-    ///   sending another frame.
-    pub fn buffer_mut(&mut self) -> Result<Buffer<'_, D, W>, SoftBufferError> {
-        Ok(Buffer {
-            buffer_impl: self.surface_impl.buffer_mut()?,
-            _marker: PhantomData,
-        })
+    /// ```
+    /// # use std::num::NonZeroU32;
+    /// # use softbuffer::Surface;
+    /// let size = NonZeroU32::new(2).unwrap();
+    /// let mut surface = Surface::new_headless(size, size).unwrap();
+    ///
+    /// let mut buffer = surface.buffer_mut().unwrap();
+    /// assert_eq!(buffer.age(), 0);
+    /// buffer.fill(0x00ff0000);
+    /// buffer.present().unwrap();
+    ///
+    /// assert_eq!(surface.fetch().unwrap(), [0x00ff0000; 4]);
+    /// assert_eq!(surface.buffer_mut().unwrap().age(), 1);
+    /// ```
+    pub fn new_headless(width: NonZeroU32, height: NonZeroU32) -> Result<Self, SoftBufferError> {
+        let mut surface_impl = backends::headless::HeadlessImpl::new_headless();
+        surface_impl.resize(width, height)?;
+
+        Ok(Self::from_dispatch(SurfaceDispatch::Headless(surface_impl)))
     }
 }
 
@@ -201,20 +1455,245 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> HasWindowHandle for Surface<D, W>
 /// - Android
 pub struct Buffer<'a, D, W> {
     buffer_impl: BufferDispatch<'a, D, W>,
+    clip_damage: bool,
+    present_log: &'a mut PresentLog,
     _marker: PhantomData<(Arc<D>, Cell<()>)>,
 }
 
-impl<D: HasDisplayHandle, W: HasWindowHandle> Buffer<'_, D, W> {
+impl<'a, D: HasDisplayHandle, W: HasWindowHandle> Buffer<'a, D, W> {
+    /// The width of the buffer, in pixels.
+    pub fn width(&self) -> NonZeroU32 {
+        self.buffer_impl.width()
+    }
+
+    /// The height of the buffer, in pixels.
+    pub fn height(&self) -> NonZeroU32 {
+        self.buffer_impl.height()
+    }
+
+    /// The number of pixels in each row of the buffer, including padding.
+    ///
+    /// This is the same as [`Buffer::width`] unless the surface was resized with
+    /// [`Surface::resize_with_stride`], or on DRM/KMS if the driver pads the rows. The pixel at
+    /// `(x, y)` is at index `y * stride + x`.
+    pub fn stride(&self) -> NonZeroU32 {
+        self.buffer_impl.stride()
+    }
+
+    /// The pixels of row `y` of the buffer, counted from the top, without any padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not less than [`Buffer::height`].
+    pub fn row(&self, y: u32) -> &[u32] {
+        let range = self.row_range(y);
+        &self.buffer_impl.pixels()[range]
+    }
+
+    /// The mutable pixels of row `y` of the buffer, counted from the top, without any padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not less than [`Buffer::height`].
+    pub fn row_mut(&mut self, y: u32) -> &mut [u32] {
+        let range = self.row_range(y);
+        &mut self.buffer_impl.pixels_mut()[range]
+    }
+
+    fn row_range(&self, y: u32) -> ops::Range<usize> {
+        let height = self.height().get();
+        assert!(
+            y < height,
+            "row {y} is out of range for a buffer of height {height}"
+        );
+        let start = y as usize * self.stride().get() as usize;
+        start..start + self.width().get() as usize
+    }
+
+    /// Split the buffer into the rows above row `y` and the rows starting at it.
+    ///
+    /// Both halves can be written to at the same time, for example to render the top and bottom
+    /// of the frame on two threads with [`std::thread::scope`], without depending on `rayon`. Each
+    /// half holds whole rows including their padding (see [`Buffer::stride`]), so the pixel at
+    /// `(x, y)` of the bottom half is at index `(y - split) * stride + x`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is greater than [`Buffer::height`].
+    pub fn split_at_row_mut(&mut self, y: u32) -> (&mut [u32], &mut [u32]) {
+        let height = self.height().get();
+        assert!(
+            y <= height,
+            "row {y} is out of range for a buffer of height {height}"
+        );
+        let stride = self.stride().get() as usize;
+        util::split_at_row_mut(self.buffer_impl.pixels_mut(), stride, y as usize)
+    }
+
+    /// The pixel at `(x, y)`, or `None` if it lies outside of the buffer.
+    ///
+    /// Unlike indexing the buffer with `y * width + x`, this takes the stride into account and
+    /// can't overflow or wrap around into the next row.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<&u32> {
+        let index = self.pixel_index(x, y)?;
+        self.buffer_impl.pixels().get(index)
+    }
+
+    /// The mutable pixel at `(x, y)`, or `None` if it lies outside of the buffer.
+    pub fn pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut u32> {
+        let index = self.pixel_index(x, y)?;
+        self.buffer_impl.pixels_mut().get_mut(index)
+    }
+
+    /// Set the pixel at `(x, y)` to `color`.
+    ///
+    /// Returns `false` without changing anything if the pixel lies outside of the buffer, which
+    /// makes this convenient for plotting shapes that are partly off-screen.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: u32) -> bool {
+        match self.pixel_mut(x, y) {
+            Some(pixel) => {
+                *pixel = color;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn pixel_index(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width().get() || y >= self.height().get() {
+            return None;
+        }
+        Some(y as usize * self.stride().get() as usize + x as usize)
+    }
+
+    fn checked_pixel_index(&self, x: u32, y: u32) -> usize {
+        self.pixel_index(x, y).unwrap_or_else(|| {
+            panic!(
+                "pixel ({x}, {y}) is out of range for a buffer of size {}x{}",
+                self.width(),
+                self.height()
+            )
+        })
+    }
+
+    /// The pixels of the buffer as bytes, four per pixel.
+    ///
+    /// The order of the channels within each pixel depends on the endianness of the target, see
+    /// [`Buffer::byte_order`].
+    pub fn as_bytes(&self) -> &[u8] {
+        let pixels = self.buffer_impl.pixels();
+        // SAFETY: Every `u32` is valid as four `u8`s, and `u8` has no alignment requirement.
+        unsafe { std::slice::from_raw_parts(pixels.as_ptr().cast(), mem::size_of_val(pixels)) }
+    }
+
+    /// The pixels of the buffer as mutable bytes, four per pixel.
+    ///
+    /// The order of the channels within each pixel depends on the endianness of the target, see
+    /// [`Buffer::byte_order`].
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        let pixels = self.buffer_impl.pixels_mut();
+        // SAFETY: Every `u32` is valid as four `u8`s and vice versa, and `u8` has no alignment
+        // requirement.
+        unsafe {
+            std::slice::from_raw_parts_mut(pixels.as_mut_ptr().cast(), mem::size_of_val(pixels))
+        }
+    }
+
+    /// A parallel iterator over the rows of the buffer, for rendering on multiple threads.
+    ///
+    /// Each item is the index of the row, counted from the top, and the pixels of that row,
+    /// including any padding at the end (see [`Buffer::stride`]).
+    #[cfg(feature = "rayon")]
+    pub fn par_rows_mut(
+        &mut self,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = (usize, &mut [u32])> {
+        use rayon::prelude::*;
+
+        let stride = self.stride().get() as usize;
+        self.buffer_impl
+            .pixels_mut()
+            .par_chunks_exact_mut(stride)
+            .enumerate()
+    }
+
+    /// The order of the channels within the bytes returned by [`Buffer::as_bytes`].
+    ///
+    /// This follows from the pixel format of the buffer being a native-endian `u32`, and is the
+    /// same for every backend.
+    pub fn byte_order(&self) -> ByteOrder {
+        if cfg!(target_endian = "little") {
+            ByteOrder::Bgrx
+        } else {
+            ByteOrder::Xrgb
+        }
+    }
+
     /// `age` is the number of frames ago this buffer was last presented. So if the value is
     /// `1`, it is the same as the last frame, and if it is `2`, it is the same as the frame
     /// before that (for backends using double buffering). If the value is `0`, it is a new
     /// buffer that has unspecified contents.
     ///
-    /// This can be used to update only a portion of the buffer.
+    /// This can be used to update only a portion of the buffer. The age never exceeds
+    /// [`Surface::buffer_count`], which is how many frames of damage have to be kept to make use
+    /// of every buffer.
     pub fn age(&self) -> u8 {
         self.buffer_impl.age()
     }
 
+    /// The regions of the buffer that are out of date compared to the last presented frame.
+    ///
+    /// A buffer with an [age](Buffer::age) of `2` or more misses the changes of the frames that
+    /// were presented after it. Softbuffer remembers the damage of every presented frame with a
+    /// [`DirtyTracker`], and returns the damage of those frames here. Redraw these regions in
+    /// addition to the damage of the new frame to bring the buffer up to date.
+    ///
+    /// Returns `None` if the whole buffer has to be redrawn, because its age is `0` or the
+    /// frames presented since are no longer remembered. Frames presented with
+    /// [`Surface::present_tile`] don't go through a buffer, and aren't taken into account.
+    ///
+    /// ```no_run
+    /// # use softbuffer::{Rect, SoftBufferError, Surface};
+    /// # use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+    /// fn draw<D: HasDisplayHandle, W: HasWindowHandle>(
+    ///     surface: &mut Surface<D, W>,
+    ///     damage: &[Rect],
+    ///     mut draw_rect: impl FnMut(&mut [u32], Rect),
+    /// ) -> Result<(), SoftBufferError> {
+    ///     let mut buffer = surface.buffer_mut()?;
+    ///     match buffer.stale_rects() {
+    ///         Some(stale) => {
+    ///             for rect in stale.iter().chain(damage) {
+    ///                 draw_rect(&mut buffer, *rect);
+    ///             }
+    ///             buffer.present_with_damage(damage)
+    ///         }
+    ///         None => {
+    ///             let full = Rect::new(0, 0, buffer.width().get(), buffer.height().get()).unwrap();
+    ///             draw_rect(&mut buffer, full);
+    ///             buffer.present()
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn stale_rects(&self) -> Option<Vec<Rect>> {
+        match self.age() {
+            0 => None,
+            // The buffer holds the last presented frame.
+            1 => Some(Vec::new()),
+            age => self.present_log.damage_history.rects_for_age(age - 1),
+        }
+    }
+
+    /// The whole buffer, as damage.
+    fn full_rect(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.width(),
+            height: self.height(),
+        }
+    }
+
     /// Presents buffer to the window.
     ///
     /// # Platform dependent behavior
@@ -228,7 +1707,36 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> Buffer<'_, D, W> {
     /// If the caller wishes to synchronize other surface/window changes, such requests must be sent to the
     /// Wayland compositor before calling this function.
     pub fn present(self) -> Result<(), SoftBufferError> {
-        self.buffer_impl.present()
+        let full = self.full_rect();
+        self.buffer_impl.present()?;
+        self.present_log.record(&[full]);
+        Ok(())
+    }
+
+    /// Track the rows that are written to, to present them as damage without having to compute
+    /// it by hand.
+    ///
+    /// This suits renderers that draw row by row. Write through [`TrackedBuffer::row_mut`], then
+    /// present with [`TrackedBuffer::present_tracked`].
+    ///
+    /// ```no_run
+    /// # use softbuffer::{SoftBufferError, Surface};
+    /// # use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+    /// fn draw_line<D: HasDisplayHandle, W: HasWindowHandle>(
+    ///     surface: &mut Surface<D, W>,
+    ///     y: u32,
+    /// ) -> Result<(), SoftBufferError> {
+    ///     let mut buffer = surface.buffer_mut()?.with_damage_tracking();
+    ///     buffer.row_mut(y).fill(0x00ff0000);
+    ///     buffer.present_tracked()
+    /// }
+    /// ```
+    pub fn with_damage_tracking(self) -> TrackedBuffer<'a, D, W> {
+        TrackedBuffer {
+            dirty_rows: vec![false; self.height().get() as usize],
+            damage: Vec::new(),
+            buffer: self,
+        }
     }
 
     /// Presents buffer to the window, with damage regions.
@@ -240,10 +1748,266 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> Buffer<'_, D, W> {
     /// - X, when XShm is available
     /// - Win32
     /// - Web
+    /// - Orbital, when the buffer size doesn't match the window size (otherwise no copy is made)
     ///
     /// Otherwise this is equivalent to [`Self::present`].
+    ///
+    /// Empty damage means that nothing changed, so the window contents aren't updated. This still
+    /// counts as presenting the buffer for [`Buffer::age`], and backends that keep several buffers
+    /// still swap them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SoftBufferError::DamageOutOfRange`] if any of the rectangles doesn't lie within
+    /// the bounds of the buffer, unless clipping was enabled with [`Surface::set_damage_clip`].
     pub fn present_with_damage(self, damage: &[Rect]) -> Result<(), SoftBufferError> {
-        self.buffer_impl.present_with_damage(damage)
+        if self.clip_damage {
+            let damage = util::clip_damage(damage, self.width(), self.height());
+            self.buffer_impl.present_with_damage(&damage)?;
+            self.present_log.record(&damage);
+            return Ok(());
+        }
+
+        util::validate_damage(damage, self.width(), self.height())?;
+        self.buffer_impl.present_with_damage(damage)?;
+        self.present_log.record(damage);
+        Ok(())
+    }
+
+    /// Like [`Buffer::present_with_damage`], but returns the regions that were actually
+    /// presented.
+    ///
+    /// This is meant for debugging, e.g. to visualize damage in an overlay. The result has
+    /// already been clipped if [`Surface::set_damage_clip`] is enabled.
+    ///
+    /// ## Platform Dependent Behavior
+    ///
+    /// - On Wayland, this is the whole buffer if the compositor doesn't support
+    ///   `wl_surface.damage_buffer`, or if the buffer scale changed.
+    /// - On Android, this is the region that `ANativeWindow_lock` asked to redraw, which contains
+    ///   the union of the damage.
+    /// - On AppKit, UIKit and Redox (when the buffer is mapped), as well as on Win32 with
+    ///   [`Surface::set_alpha_blending`], this is always the whole buffer.
+    /// - On all other platforms, this is the damage as given.
+    pub fn present_with_damage_reported(
+        self,
+        damage: &[Rect],
+    ) -> Result<Vec<Rect>, SoftBufferError> {
+        if self.clip_damage {
+            let damage = util::clip_damage(damage, self.width(), self.height());
+            let reported = self.buffer_impl.present_with_damage_reported(&damage)?;
+            self.present_log.record(&damage);
+            return Ok(reported);
+        }
+
+        util::validate_damage(damage, self.width(), self.height())?;
+        let reported = self.buffer_impl.present_with_damage_reported(damage)?;
+        self.present_log.record(damage);
+        Ok(reported)
+    }
+
+    /// Presents buffer to the window, then reads back the window contents like
+    /// [`Surface::fetch`].
+    ///
+    /// Unlike calling [`Surface::fetch`] after [`Self::present`], this waits for the backend to
+    /// finish presenting where necessary, so the returned pixels include this buffer.
+    ///
+    /// # Platform dependent behavior
+    ///
+    /// Supported on:
+    /// - X
+    /// - Win32
+    /// - Web
+    /// - Headless
+    ///
+    /// Elsewhere the buffer is still presented, but [`SoftBufferError::Unimplemented`] is
+    /// returned.
+    pub fn present_and_fetch(self) -> Result<Vec<u32>, SoftBufferError> {
+        let full = self.full_rect();
+        match self.buffer_impl.present_and_fetch() {
+            Ok(pixels) => {
+                self.present_log.record(&[full]);
+                Ok(pixels)
+            }
+            // The buffer was presented even though fetching failed.
+            Err(PresentAndFetchError::Fetch(err)) => {
+                self.present_log.record(&[full]);
+                Err(err)
+            }
+            Err(PresentAndFetchError::Present(err)) => Err(err),
+        }
+    }
+
+    /// Presents buffer to the window, damaging only the pixels that differ from `previous`.
+    ///
+    /// `previous` should be the frame that is currently shown in the window, in the same format
+    /// as this buffer. The damage is computed with [`diff_damage`]. If `previous` has a different
+    /// size than this buffer, the whole buffer is presented like with [`Self::present`]. If
+    /// nothing changed, the buffer isn't presented at all.
+    pub fn present_auto_damage(self, previous: &[u32]) -> Result<(), SoftBufferError> {
+        if previous.len() != self.len() {
+            return self.present();
+        }
+
+        // Differences in the padding at the end of each row aren't shown, so ignore them.
+        let width = self.width().get();
+        let damage: Vec<_> = diff_damage(previous, &self, self.stride(), 0)
+            .into_iter()
+            .filter_map(|rect| {
+                let visible_width = rect.width.get().min(width.saturating_sub(rect.x));
+                Rect::new(rect.x, rect.y, visible_width, rect.height.get())
+            })
+            .collect();
+        if damage.is_empty() {
+            return Ok(());
+        }
+
+        self.present_with_damage(&damage)
+    }
+
+    /// Fill the rectangle `rect` of the buffer with `color`.
+    ///
+    /// The part of `rect` that lies outside of the buffer is ignored, so this doesn't panic for
+    /// rectangles that are too large. Each row is filled at once, which is much faster than
+    /// setting the pixels one by one.
+    pub fn fill_rect(&mut self, rect: Rect, color: u32) {
+        let Some(rect) = rect.intersection(&self.full_rect()) else {
+            return;
+        };
+
+        let stride = self.stride().get() as usize;
+        let (x, width) = (rect.x as usize, rect.width.get() as usize);
+        let pixels = self.buffer_impl.pixels_mut();
+        for y in rect.y as usize..(rect.y + rect.height.get()) as usize {
+            let start = y * stride + x;
+            pixels[start..start + width].fill(color);
+        }
+    }
+
+    /// Copy the `src` rectangle of the buffer so that its top-left corner ends up at `dst`.
+    ///
+    /// This is useful for scrolling: shift the existing contents, draw the newly exposed area,
+    /// and present both with [`Buffer::present_with_damage`]. The areas may overlap, and are
+    /// clipped to the bounds of the buffer.
+    pub fn copy_within_rect(&mut self, src: Rect, dst: (u32, u32)) {
+        let size = (
+            self.width().get(),
+            self.height().get(),
+            self.stride().get() as usize,
+        );
+        util::copy_within_rect(self.buffer_impl.pixels_mut(), size, src, dst);
+    }
+
+    /// Copy an [`image::RgbaImage`] into the buffer, with its top-left corner at `dst.x`/`dst.y`.
+    ///
+    /// At most `dst.width` by `dst.height` pixels of the image are copied. The area is clipped
+    /// to the bounds of both the buffer and the image; if nothing is left after clipping, this
+    /// does nothing. The alpha channel of the image is ignored.
+    ///
+    /// The pixels are converted to the format described in the [`Buffer`] documentation, so the
+    /// result is the same regardless of the platform's byte order.
+    #[cfg(feature = "image")]
+    pub fn copy_from_image(&mut self, image: &image::RgbaImage, dst: Rect) {
+        self.draw_image(image, dst, |_, [r, g, b, _]| {
+            (u32::from(r) << 16) | (u32::from(g) << 8) | u32::from(b)
+        });
+    }
+
+    /// Like [`Buffer::copy_from_image`], but composites the image over the existing contents of
+    /// the buffer using its (straight) alpha channel.
+    #[cfg(feature = "image")]
+    pub fn blend_from_image(&mut self, image: &image::RgbaImage, dst: Rect) {
+        self.draw_image(image, dst, |old, rgba| {
+            util::blend_straight(
+                old,
+                u32::from_be_bytes([rgba[3], rgba[0], rgba[1], rgba[2]]),
+            )
+        });
+    }
+
+    /// Composites the pixels in `src` over the rectangle `dst` of the buffer.
+    ///
+    /// `src` holds `dst.width * dst.height` pixels in rows without padding, in the same format
+    /// as the buffer, except that the upper 8 bits are the alpha channel (`0xAARRGGBB`). The
+    /// buffer is treated as opaque. The part of `dst` that lies outside of the buffer is ignored.
+    ///
+    /// This is meant for translucent overlays, like tooltips or dimming the contents behind a
+    /// dialog.
+    ///
+    /// # Panics
+    ///
+    /// If the length of `src` doesn't match the area of `dst`.
+    pub fn blend_rect(&mut self, src: &[u32], src_alpha_mode: AlphaMode, dst: Rect) {
+        assert_eq!(
+            src.len() as u64,
+            dst.area(),
+            "the source pixels don't match the destination"
+        );
+
+        // Clip the destination to the buffer.
+        let width = dst
+            .width
+            .get()
+            .min(self.width().get().saturating_sub(dst.x)) as usize;
+        let height = dst
+            .height
+            .get()
+            .min(self.height().get().saturating_sub(dst.y));
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let stride = self.stride().get() as usize;
+        let pixels = self.buffer_impl.pixels_mut();
+        for (y, src) in src
+            .chunks_exact(dst.width.get() as usize)
+            .take(height as usize)
+            .enumerate()
+        {
+            let start = (dst.y as usize + y) * stride + dst.x as usize;
+            util::blend_row(
+                &mut pixels[start..start + width],
+                &src[..width],
+                src_alpha_mode,
+            );
+        }
+    }
+
+    #[cfg(feature = "image")]
+    fn draw_image(
+        &mut self,
+        image: &image::RgbaImage,
+        dst: Rect,
+        mut pixel: impl FnMut(u32, [u8; 4]) -> u32,
+    ) {
+        let buffer_width = self.width().get();
+        let buffer_height = self.height().get();
+
+        // Clip the destination to the buffer and to the image.
+        let width = dst
+            .width
+            .get()
+            .min(image.width())
+            .min(buffer_width.saturating_sub(dst.x));
+        let height = dst
+            .height
+            .get()
+            .min(image.height())
+            .min(buffer_height.saturating_sub(dst.y));
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let stride = self.stride().get() as usize;
+        let pixels = self.buffer_impl.pixels_mut();
+        for y in 0..height {
+            let start = (dst.y + y) as usize * stride + dst.x as usize;
+            let row = &mut pixels[start..start + width as usize];
+            for (x, dst) in row.iter_mut().enumerate() {
+                let image::Rgba(src) = *image.get_pixel(x as u32, y);
+                *dst = pixel(*dst, src);
+            }
+        }
     }
 }
 
@@ -263,6 +2027,233 @@ impl<D: HasDisplayHandle, W: HasWindowHandle> ops::DerefMut for Buffer<'_, D, W>
     }
 }
 
+/// Index the pixel at `(x, y)`, taking the stride into account.
+///
+/// ```no_run
+/// # use softbuffer::{SoftBufferError, Surface};
+/// # use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+/// fn draw_diagonal<D: HasDisplayHandle, W: HasWindowHandle>(
+///     surface: &mut Surface<D, W>,
+/// ) -> Result<(), SoftBufferError> {
+///     let mut buffer = surface.buffer_mut()?;
+///     let size = buffer.width().min(buffer.height()).get();
+///     for i in 0..size {
+///         buffer[(i, i)] = 0x00ffffff;
+///     }
+///     buffer.present()
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics if the pixel lies outside of the buffer.
+impl<D: HasDisplayHandle, W: HasWindowHandle> ops::Index<(u32, u32)> for Buffer<'_, D, W> {
+    type Output = u32;
+
+    #[inline]
+    fn index(&self, (x, y): (u32, u32)) -> &u32 {
+        let index = self.checked_pixel_index(x, y);
+        &self.buffer_impl.pixels()[index]
+    }
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> ops::IndexMut<(u32, u32)> for Buffer<'_, D, W> {
+    #[inline]
+    fn index_mut(&mut self, (x, y): (u32, u32)) -> &mut u32 {
+        let index = self.checked_pixel_index(x, y);
+        &mut self.buffer_impl.pixels_mut()[index]
+    }
+}
+
+// Indexing by `(x, y)` hides the indexing of the pixel slice behind `Deref`, so forward it.
+macro_rules! forward_slice_index {
+    ($($index:ty => $output:ty),* $(,)?) => {$(
+        impl<D: HasDisplayHandle, W: HasWindowHandle> ops::Index<$index> for Buffer<'_, D, W> {
+            type Output = $output;
+
+            #[inline]
+            fn index(&self, index: $index) -> &$output {
+                &self.buffer_impl.pixels()[index]
+            }
+        }
+
+        impl<D: HasDisplayHandle, W: HasWindowHandle> ops::IndexMut<$index> for Buffer<'_, D, W> {
+            #[inline]
+            fn index_mut(&mut self, index: $index) -> &mut $output {
+                &mut self.buffer_impl.pixels_mut()[index]
+            }
+        }
+    )*};
+}
+
+forward_slice_index! {
+    usize => u32,
+    ops::Range<usize> => [u32],
+    ops::RangeFrom<usize> => [u32],
+    ops::RangeTo<usize> => [u32],
+    ops::RangeInclusive<usize> => [u32],
+    ops::RangeToInclusive<usize> => [u32],
+    ops::RangeFull => [u32],
+}
+
+/// A buffer whose contents may be uninitialized, returned by [`Surface::buffer_mut_uninit`].
+///
+/// This dereferences to the pixels as `[MaybeUninit<u32>]`, see [`Buffer`] for their layout.
+pub struct UninitBuffer<'a, D, W> {
+    buffer: Buffer<'a, D, W>,
+}
+
+impl<'a, D: HasDisplayHandle, W: HasWindowHandle> UninitBuffer<'a, D, W> {
+    /// The width of the buffer, in pixels.
+    pub fn width(&self) -> NonZeroU32 {
+        self.buffer.width()
+    }
+
+    /// The height of the buffer, in pixels.
+    pub fn height(&self) -> NonZeroU32 {
+        self.buffer.height()
+    }
+
+    /// The number of pixels in each row of the buffer, see [`Buffer::stride`].
+    pub fn stride(&self) -> NonZeroU32 {
+        self.buffer.stride()
+    }
+
+    /// Converts this into a [`Buffer`] that can be read and presented.
+    ///
+    /// # Safety
+    ///
+    /// Every pixel of the buffer must have been written, including the padding at the end of
+    /// the rows if the stride is larger than the width.
+    pub unsafe fn assume_init(self) -> Buffer<'a, D, W> {
+        self.buffer
+    }
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> ops::Deref for UninitBuffer<'_, D, W> {
+    type Target = [MaybeUninit<u32>];
+
+    #[inline]
+    fn deref(&self) -> &[MaybeUninit<u32>] {
+        self.buffer.buffer_impl.pixels_uninit()
+    }
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> ops::DerefMut for UninitBuffer<'_, D, W> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [MaybeUninit<u32>] {
+        self.buffer.buffer_impl.pixels_uninit_mut()
+    }
+}
+
+/// A buffer together with the damage that was done to it, returned by [`Surface::begin_frame`].
+///
+/// When dropped, the buffer is presented with the accumulated damage.
+pub struct Frame<'a, D: HasDisplayHandle, W: HasWindowHandle> {
+    /// This is only `None` after the frame has been presented.
+    buffer: Option<Buffer<'a, D, W>>,
+    damage: Vec<Rect>,
+}
+
+impl<'a, D: HasDisplayHandle, W: HasWindowHandle> Frame<'a, D, W> {
+    /// The buffer that the frame is drawn to.
+    pub fn buffer(&self) -> &Buffer<'a, D, W> {
+        self.buffer.as_ref().unwrap()
+    }
+
+    /// The buffer that the frame is drawn to, mutably.
+    pub fn buffer_mut(&mut self) -> &mut Buffer<'a, D, W> {
+        self.buffer.as_mut().unwrap()
+    }
+
+    /// Mark the given region as changed in this frame.
+    pub fn add_damage(&mut self, rect: Rect) {
+        self.damage.push(rect);
+    }
+
+    /// The damage accumulated so far.
+    pub fn damage(&self) -> &[Rect] {
+        &self.damage
+    }
+
+    /// Presents the buffer with the accumulated damage.
+    ///
+    /// See [`Buffer::present_with_damage`] for details. Unlike dropping the frame, this reports
+    /// errors.
+    pub fn present(mut self) -> Result<(), SoftBufferError> {
+        let buffer = self.buffer.take().unwrap();
+        buffer.present_with_damage(&self.damage)
+    }
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> Drop for Frame<'_, D, W> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            if let Err(err) = buffer.present_with_damage(&self.damage) {
+                tracing::warn!("failed to present dropped frame: {err}");
+            }
+        }
+    }
+}
+
+/// A buffer that keeps track of the rows that were written to, returned by
+/// [`Buffer::with_damage_tracking`].
+///
+/// The pixels can be read through [`Deref`](ops::Deref) to the [`Buffer`], but only written to
+/// through [`TrackedBuffer::row_mut`] and [`TrackedBuffer::touch`], so that no write goes
+/// unnoticed.
+pub struct TrackedBuffer<'a, D, W> {
+    buffer: Buffer<'a, D, W>,
+    dirty_rows: Vec<bool>,
+    damage: Vec<Rect>,
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> TrackedBuffer<'_, D, W> {
+    /// The mutable pixels of row `y` of the buffer, which is marked as changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not less than [`Buffer::height`].
+    pub fn row_mut(&mut self, y: u32) -> &mut [u32] {
+        let row = self.buffer.row_mut(y);
+        self.dirty_rows[y as usize] = true;
+        row
+    }
+
+    /// Mark `rect` as changed, and get mutable access to the whole buffer to change it.
+    ///
+    /// Writing outside of `rect` through the returned buffer isn't tracked.
+    pub fn touch(&mut self, rect: Rect) -> &mut [u32] {
+        self.damage.push(rect);
+        &mut self.buffer
+    }
+
+    /// The damage accumulated so far, with changed rows coalesced into full-width rectangles.
+    pub fn damage(&self) -> Vec<Rect> {
+        let mut damage = util::row_damage(&self.dirty_rows, self.buffer.width());
+        damage.extend_from_slice(&self.damage);
+        damage
+    }
+
+    /// Presents the buffer with the accumulated damage.
+    ///
+    /// See [`Buffer::present_with_damage`] for details. If nothing was written, the window
+    /// contents aren't updated.
+    pub fn present_tracked(self) -> Result<(), SoftBufferError> {
+        let damage = self.damage();
+        self.buffer.present_with_damage(&damage)
+    }
+}
+
+impl<'a, D: HasDisplayHandle, W: HasWindowHandle> ops::Deref for TrackedBuffer<'a, D, W> {
+    type Target = Buffer<'a, D, W>;
+
+    #[inline]
+    fn deref(&self) -> &Buffer<'a, D, W> {
+        &self.buffer
+    }
+}
+
 /// There is no display handle.
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -335,6 +2326,22 @@ fn __assert_send() {
     is_send::<Surface<(), ()>>();
     is_send::<Buffer<'static, (), ()>>();
 
+    // The guarantees documented on `Context` and `Surface` hold for any display and window.
+    fn __generic<D: Send + Sync, W: Send>() {
+        is_send::<Context<D>>();
+        is_sync::<Context<D>>();
+        is_send::<Surface<D, W>>();
+    }
+
+    /// ```compile_fail
+    /// use softbuffer::Context;
+    /// use std::rc::Rc;
+    ///
+    /// fn __is_send<T: Send>() {}
+    /// __is_send::<Context<Rc<()>>>();
+    /// ```
+    fn __context_not_send_with_rc() {}
+
     /// ```compile_fail
     /// use softbuffer::Surface;
     ///
@@ -2,8 +2,10 @@
 #![allow(dead_code)]
 
 use std::cmp;
+use std::collections::VecDeque;
 use std::num::NonZeroU32;
 
+use crate::AlphaMode;
 use crate::Rect;
 use crate::SoftBufferError;
 
@@ -52,6 +54,58 @@ impl<'a, T: 'a + ?Sized, U: 'a + ?Sized> BorrowStack<'a, T, U> {
 
 /// Calculates the smallest `Rect` necessary to represent all damaged `Rect`s.
 pub(crate) fn union_damage(damage: &[Rect]) -> Option<Rect> {
+    damage
+        .iter()
+        .copied()
+        .reduce(|prev, next| prev.union(&next))
+}
+
+/// Checks that every damaged `Rect` lies within a buffer of the given size.
+pub(crate) fn validate_damage(
+    damage: &[Rect],
+    width: NonZeroU32,
+    height: NonZeroU32,
+) -> Result<(), SoftBufferError> {
+    for rect in damage {
+        let right = rect.x.checked_add(rect.width.get());
+        let bottom = rect.y.checked_add(rect.height.get());
+        if !right.is_some_and(|right| right <= width.get())
+            || !bottom.is_some_and(|bottom| bottom <= height.get())
+        {
+            return Err(SoftBufferError::DamageOutOfRange { rect: *rect });
+        }
+    }
+
+    Ok(())
+}
+
+/// Intersects every damaged `Rect` with a buffer of the given size, dropping empty results.
+pub(crate) fn clip_damage(damage: &[Rect], width: NonZeroU32, height: NonZeroU32) -> Vec<Rect> {
+    let bounds = Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    damage
+        .iter()
+        .filter_map(|rect| rect.intersection(&bounds))
+        .collect()
+}
+
+/// Compares two frames and returns rectangles covering every pixel that differs between them.
+///
+/// Both frames are tightly packed rows of `width` pixels, like [`Buffer`](crate::Buffer). The
+/// result can be passed to [`Buffer::present_with_damage`](crate::Buffer::present_with_damage).
+///
+/// Each row is scanned for the leftmost and rightmost changed pixel, and rows with changes are
+/// then merged into blocks. Two changed rows end up in the same rectangle if at most `max_gap`
+/// unchanged rows lie between them. A higher `max_gap` produces fewer, larger rectangles.
+///
+/// # Panics
+///
+/// Panics if `old` and `new` have different lengths.
+pub fn diff_damage(old: &[u32], new: &[u32], width: NonZeroU32, max_gap: u32) -> Vec<Rect> {
     struct Region {
         left: u32,
         top: u32,
@@ -59,30 +113,276 @@ pub(crate) fn union_damage(damage: &[Rect]) -> Option<Rect> {
         right: u32,
     }
 
-    let region = damage
-        .iter()
-        .map(|rect| Region {
-            left: rect.x,
-            top: rect.y,
-            right: rect.x + rect.width.get(),
-            bottom: rect.y + rect.height.get(),
-        })
-        .reduce(|mut prev, next| {
-            prev.left = cmp::min(prev.left, next.left);
-            prev.top = cmp::min(prev.top, next.top);
-            prev.right = cmp::max(prev.right, next.right);
-            prev.bottom = cmp::max(prev.bottom, next.bottom);
-            prev
-        })?;
-
-    Some(Rect {
-        x: region.left,
-        y: region.top,
-        width: NonZeroU32::new(region.right - region.left)
-            .expect("`right` must always be bigger then `left`"),
-        height: NonZeroU32::new(region.bottom - region.top)
-            .expect("`bottom` must always be bigger then `top`"),
-    })
+    impl Region {
+        fn to_rect(&self) -> Rect {
+            Rect {
+                x: self.left,
+                y: self.top,
+                width: NonZeroU32::new(self.right - self.left).unwrap(),
+                height: NonZeroU32::new(self.bottom - self.top).unwrap(),
+            }
+        }
+    }
+
+    assert_eq!(
+        old.len(),
+        new.len(),
+        "the frames passed to `diff_damage` must have the same size"
+    );
+
+    let width = width.get() as usize;
+    let mut rects = Vec::new();
+    let mut current: Option<Region> = None;
+
+    for (y, (old_row, new_row)) in old
+        .chunks_exact(width)
+        .zip(new.chunks_exact(width))
+        .enumerate()
+    {
+        let changed = |(old, new): (&u32, &u32)| old != new;
+        let Some(left) = old_row.iter().zip(new_row).position(changed) else {
+            continue;
+        };
+        let right = width - old_row.iter().zip(new_row).rev().position(changed).unwrap();
+        let (left, right, y) = (left as u32, right as u32, y as u32);
+
+        match &mut current {
+            Some(region) if y - region.bottom <= max_gap => {
+                region.left = cmp::min(region.left, left);
+                region.right = cmp::max(region.right, right);
+                region.bottom = y + 1;
+            }
+            _ => {
+                rects.extend(current.as_ref().map(Region::to_rect));
+                current = Some(Region {
+                    left,
+                    top: y,
+                    bottom: y + 1,
+                    right,
+                });
+            }
+        }
+    }
+
+    rects.extend(current.as_ref().map(Region::to_rect));
+    rects
+}
+
+/// Copies the top-left region of `src` that fits into `dst`, and zeroes the rest of `dst`.
+///
+/// Both buffers are tightly packed rows of `src_width` and `dst_width` pixels respectively.
+pub(crate) fn copy_overlapping(src: &[u32], src_width: usize, dst: &mut [u32], dst_width: usize) {
+    let width = cmp::min(src_width, dst_width);
+    let mut src_rows = src.chunks_exact(src_width);
+    for dst_row in dst.chunks_exact_mut(dst_width) {
+        match src_rows.next() {
+            Some(src_row) => {
+                dst_row[..width].copy_from_slice(&src_row[..width]);
+                dst_row[width..].fill(0);
+            }
+            None => dst_row.fill(0),
+        }
+    }
+}
+
+/// Resizes `buffer` to `len` pixels, without giving back memory when it shrinks.
+///
+/// Like shared memory segments, the allocation grows to the next power of two, so that a window
+/// whose size oscillates during an interactive resize doesn't reallocate every time.
+pub(crate) fn resize_buffer(buffer: &mut Vec<u32>, len: usize) {
+    if len > buffer.capacity() {
+        buffer.reserve_exact(len.next_power_of_two() - buffer.len());
+    }
+    buffer.resize(len, 0);
+}
+
+/// Splits a buffer with rows of `stride` pixels before row `y`.
+///
+/// The second half starts at row `y`, and is empty if `y` is the height of the buffer.
+pub(crate) fn split_at_row_mut(
+    pixels: &mut [u32],
+    stride: usize,
+    y: usize,
+) -> (&mut [u32], &mut [u32]) {
+    // The last row of some buffers isn't padded up to the stride.
+    let mid = cmp::min(y * stride, pixels.len());
+    pixels.split_at_mut(mid)
+}
+
+/// Turns runs of dirty rows into full-width damage rectangles.
+pub(crate) fn row_damage(dirty_rows: &[bool], width: NonZeroU32) -> Vec<Rect> {
+    let mut damage = Vec::new();
+    let mut start = None;
+    for (y, &dirty) in dirty_rows.iter().chain(&[false]).enumerate() {
+        match (start, dirty) {
+            (None, true) => start = Some(y as u32),
+            (Some(y0), false) => {
+                damage.extend(Rect::from_bounds(0, y0, width.get(), y as u32));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    damage
+}
+
+/// Copies the `src` rectangle of a buffer so that its top-left corner ends up at `dst`.
+///
+/// The buffer has `height` rows of `stride` pixels, of which the first `width` are visible. Both
+/// areas are clipped to the visible part of the buffer, and may overlap.
+pub(crate) fn copy_within_rect(
+    buffer: &mut [u32],
+    (width, height, stride): (u32, u32, usize),
+    src: Rect,
+    (dst_x, dst_y): (u32, u32),
+) {
+    let copy_width = src
+        .width
+        .get()
+        .min(width.saturating_sub(src.x))
+        .min(width.saturating_sub(dst_x)) as usize;
+    let copy_height = src
+        .height
+        .get()
+        .min(height.saturating_sub(src.y))
+        .min(height.saturating_sub(dst_y)) as usize;
+    if copy_width == 0 || copy_height == 0 {
+        return;
+    }
+
+    let copy_row = |buffer: &mut [u32], row: usize| {
+        let src_start = (src.y as usize + row) * stride + src.x as usize;
+        let dst_start = (dst_y as usize + row) * stride + dst_x as usize;
+        buffer.copy_within(src_start..src_start + copy_width, dst_start);
+    };
+
+    // Like `memmove`, copy the rows in the order that doesn't overwrite rows that haven't been
+    // copied yet. Overlap within a row is handled by `copy_within`.
+    if dst_y > src.y {
+        (0..copy_height).rev().for_each(|row| copy_row(buffer, row));
+    } else {
+        (0..copy_height).for_each(|row| copy_row(buffer, row));
+    }
+}
+
+/// Composites the `0xAARRGGBB` pixels of `src` over the opaque pixels of `dst`.
+///
+/// The mode is matched once per row, so that the loops can be vectorized.
+pub(crate) fn blend_row(dst: &mut [u32], src: &[u32], mode: AlphaMode) {
+    let pairs = dst.iter_mut().zip(src);
+    match mode {
+        AlphaMode::Straight => pairs.for_each(|(dst, &src)| *dst = blend_straight(*dst, src)),
+        AlphaMode::Premultiplied => {
+            pairs.for_each(|(dst, &src)| *dst = blend_premultiplied(*dst, src))
+        }
+    }
+}
+
+/// Source-over compositing of a pixel with straight alpha onto an opaque pixel.
+#[inline]
+pub(crate) fn blend_straight(dst: u32, src: u32) -> u32 {
+    let a = src >> 24;
+    let blend = |shift: u32| {
+        let (src, dst) = ((src >> shift) & 0xff, (dst >> shift) & 0xff);
+        ((src * a + dst * (255 - a) + 127) / 255) << shift
+    };
+    blend(16) | blend(8) | blend(0)
+}
+
+/// Source-over compositing of a pixel with premultiplied alpha onto an opaque pixel.
+#[inline]
+pub(crate) fn blend_premultiplied(dst: u32, src: u32) -> u32 {
+    let a = src >> 24;
+    let blend = |shift: u32| {
+        let (src, dst) = ((src >> shift) & 0xff, (dst >> shift) & 0xff);
+        // Invalid premultiplied pixels, with a color larger than the alpha, can overflow.
+        cmp::min(src + (dst * (255 - a) + 127) / 255, 255) << shift
+    };
+    blend(16) | blend(8) | blend(0)
+}
+
+/// Remembers the damage of recent frames, to find out what to redraw based on [`Buffer::age`].
+///
+/// A buffer with an age of `n` contains the frame that was presented `n` frames ago, so
+/// everything that changed in the `n` frames since then has to be redrawn. Call
+/// [`DirtyTracker::record`] with the damage of every frame before drawing it, then redraw the
+/// rectangles returned by [`DirtyTracker::rects_for_age`].
+///
+/// [`Buffer::age`]: crate::Buffer::age
+///
+/// ```
+/// # use softbuffer::{DirtyTracker, Rect};
+/// let mut tracker = DirtyTracker::new(2);
+///
+/// let first = Rect::new(0, 0, 10, 10).unwrap();
+/// tracker.record(&[first]);
+/// let second = Rect::new(20, 0, 10, 10).unwrap();
+/// tracker.record(&[second]);
+///
+/// // The buffer contains the previous frame, so only the current damage has to be redrawn.
+/// assert_eq!(tracker.rects_for_age(1).unwrap().len(), 1);
+/// // The buffer contains the frame before that, so both frames have to be redrawn.
+/// assert_eq!(tracker.rects_for_age(2).unwrap().len(), 2);
+/// // The buffer is new, or older than what was recorded, so everything has to be redrawn.
+/// assert!(tracker.rects_for_age(0).is_none());
+/// assert!(tracker.rects_for_age(3).is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirtyTracker {
+    /// The damage of the most recent frames, oldest first.
+    frames: VecDeque<Vec<Rect>>,
+    max_age: u8,
+}
+
+impl DirtyTracker {
+    /// Creates a tracker that remembers the damage of the last `max_age` frames.
+    ///
+    /// Buffers older than that are redrawn completely. Most backends use at most two or three
+    /// buffers, so a small `max_age` is enough.
+    pub fn new(max_age: u8) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(max_age.into()),
+            max_age,
+        }
+    }
+
+    /// Records the damage of the frame that is about to be drawn.
+    pub fn record(&mut self, rects: &[Rect]) {
+        if self.max_age == 0 {
+            return;
+        }
+
+        if self.frames.len() == usize::from(self.max_age) {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(rects.to_vec());
+    }
+
+    /// The rectangles to redraw in a buffer of the given age, which is the damage of the last
+    /// `age` recorded frames, including the current one.
+    ///
+    /// Returns `None` if the whole buffer has to be redrawn, because its age is `0` or older than
+    /// the frames that were recorded.
+    pub fn rects_for_age(&self, age: u8) -> Option<Vec<Rect>> {
+        let age = usize::from(age);
+        if age == 0 || age > self.frames.len() {
+            return None;
+        }
+
+        let rects = self
+            .frames
+            .iter()
+            .skip(self.frames.len() - age)
+            .flatten()
+            .copied()
+            .collect();
+        Some(rects)
+    }
+
+    /// Forgets all recorded frames, e.g. after the surface was resized.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
 }
 
 #[cfg(test)]
@@ -101,4 +401,248 @@ mod tests {
         f(BorrowStack::new(v.as_mut(), |v: &mut [u32]| Ok(&mut v[2])).unwrap());
         assert_eq!(&v, &[1, 2, 42, 4, 5]);
     }
+
+    #[test]
+    fn test_validate_damage() {
+        let size = NonZeroU32::new(10).unwrap();
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width: NonZeroU32::new(width).unwrap(),
+            height: NonZeroU32::new(height).unwrap(),
+        };
+
+        assert!(validate_damage(&[], size, size).is_ok());
+        assert!(validate_damage(&[rect(0, 0, 10, 10)], size, size).is_ok());
+        assert!(validate_damage(&[rect(9, 9, 1, 1)], size, size).is_ok());
+
+        // Straddling the right edge.
+        assert!(matches!(
+            validate_damage(&[rect(0, 0, 1, 1), rect(5, 0, 6, 1)], size, size),
+            Err(SoftBufferError::DamageOutOfRange { rect }) if rect.x == 5
+        ));
+        // Straddling the bottom edge.
+        assert!(matches!(
+            validate_damage(&[rect(0, 9, 1, 2)], size, size),
+            Err(SoftBufferError::DamageOutOfRange { .. })
+        ));
+        // Overflowing `u32`.
+        assert!(matches!(
+            validate_damage(&[rect(u32::MAX, 0, 1, 1)], size, size),
+            Err(SoftBufferError::DamageOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_diff_damage() {
+        let width = NonZeroU32::new(4).unwrap();
+        let old = [0; 16];
+        #[rustfmt::skip]
+        let new = [
+            0, 1, 0, 0,
+            0, 0, 1, 0,
+            0, 0, 0, 0,
+            1, 0, 0, 0,
+        ];
+
+        let rects = |max_gap| {
+            diff_damage(&old, &new, width, max_gap)
+                .iter()
+                .map(|r| (r.x, r.y, r.width.get(), r.height.get()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rects(0), [(1, 0, 2, 2), (0, 3, 1, 1)]);
+        assert_eq!(rects(1), [(0, 0, 3, 4)]);
+        assert!(diff_damage(&old, &old, width, 0).is_empty());
+    }
+
+    #[test]
+    fn test_clip_damage() {
+        let (width, height) = (NonZeroU32::new(4).unwrap(), NonZeroU32::new(3).unwrap());
+        let damage = [
+            Rect::new(1, 1, 2, 2).unwrap(),
+            Rect::new(2, 2, 5, 5).unwrap(),
+            Rect::new(4, 0, 1, 1).unwrap(),
+            Rect::new(u32::MAX, 0, 1, 1).unwrap(),
+        ];
+
+        let clipped = clip_damage(&damage, width, height)
+            .iter()
+            .map(|r| (r.x, r.y, r.width.get(), r.height.get()))
+            .collect::<Vec<_>>();
+        assert_eq!(clipped, [(1, 1, 2, 2), (2, 2, 2, 1)]);
+    }
+
+    #[test]
+    fn test_rect_overflow() {
+        let edge = Rect::new(u32::MAX - 1, u32::MAX - 1, u32::MAX, 2).unwrap();
+        assert!(edge.contains_point(u32::MAX, u32::MAX));
+        assert!(!edge.contains_point(u32::MAX - 2, u32::MAX));
+        assert_eq!(edge.area(), u64::from(u32::MAX) * 2);
+
+        let origin = Rect::new(0, 0, 1, 1).unwrap();
+        let union = origin.union(&edge);
+        assert_eq!(
+            (union.width.get(), union.height.get()),
+            (u32::MAX, u32::MAX)
+        );
+
+        let both = edge.intersection(&Rect::new(u32::MAX, 0, 1, u32::MAX).unwrap());
+        let both = both.map(|r| (r.x, r.y, r.width.get(), r.height.get()));
+        assert_eq!(both, Some((u32::MAX, u32::MAX - 1, 1, 1)));
+        assert!(origin.intersection(&edge).is_none());
+    }
+
+    #[test]
+    fn test_split_at_row_mut() {
+        let mut pixels: Vec<u32> = (0..12).collect();
+        for y in 0..=3 {
+            let (top, bottom) = split_at_row_mut(&mut pixels, 4, y);
+            assert_eq!(top.len(), y * 4);
+            let covered = top.iter().chain(bottom.iter()).copied().collect::<Vec<_>>();
+            assert_eq!(covered, (0..12).collect::<Vec<_>>());
+        }
+
+        // The halves can be written to independently.
+        let (top, bottom) = split_at_row_mut(&mut pixels, 4, 1);
+        top.fill(1);
+        bottom.fill(2);
+        assert_eq!(pixels, [[1; 4], [2; 4], [2; 4]].concat());
+    }
+
+    #[test]
+    fn test_resize_buffer() {
+        let mut buffer = Vec::new();
+        resize_buffer(&mut buffer, 100);
+        assert_eq!(buffer.len(), 100);
+        let capacity = buffer.capacity();
+        assert!(capacity >= 128);
+
+        // Neither shrinking nor growing within the capacity reallocates.
+        let ptr = buffer.as_ptr();
+        resize_buffer(&mut buffer, 10);
+        resize_buffer(&mut buffer, 120);
+        assert_eq!(buffer.len(), 120);
+        assert_eq!((buffer.as_ptr(), buffer.capacity()), (ptr, capacity));
+        assert!(buffer[10..].iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn test_row_damage() {
+        let width = NonZeroU32::new(8).unwrap();
+        let rects = |dirty_rows: &[bool]| {
+            row_damage(dirty_rows, width)
+                .iter()
+                .map(|r| (r.x, r.y, r.width.get(), r.height.get()))
+                .collect::<Vec<_>>()
+        };
+        assert!(rects(&[false; 4]).is_empty());
+        assert_eq!(
+            rects(&[true, true, false, true]),
+            [(0, 0, 8, 2), (0, 3, 8, 1)]
+        );
+        assert_eq!(rects(&[true; 3]), [(0, 0, 8, 3)]);
+    }
+
+    #[test]
+    fn test_copy_within_rect() {
+        #[rustfmt::skip]
+        let original = [
+            1, 2, 3,
+            4, 5, 6,
+            7, 8, 9,
+        ];
+        let copy = |src, dst| {
+            let mut buffer = original;
+            copy_within_rect(&mut buffer, (3, 3, 3), src, dst);
+            buffer
+        };
+
+        // Scroll up by one row, overlapping.
+        let src = Rect::new(0, 1, 3, 2).unwrap();
+        assert_eq!(copy(src, (0, 0)), [4, 5, 6, 7, 8, 9, 7, 8, 9]);
+
+        // Scroll down by one row, overlapping.
+        let src = Rect::new(0, 0, 3, 2).unwrap();
+        assert_eq!(copy(src, (0, 1)), [1, 2, 3, 1, 2, 3, 4, 5, 6]);
+
+        // Shift diagonally, clipped to the buffer.
+        let src = Rect::new(0, 0, 3, 3).unwrap();
+        assert_eq!(copy(src, (1, 1)), [1, 2, 3, 4, 1, 2, 7, 4, 5]);
+
+        // Nothing is left after clipping.
+        let src = Rect::new(3, 0, 1, 1).unwrap();
+        assert_eq!(copy(src, (0, 0)), original);
+    }
+
+    #[test]
+    fn test_dirty_tracker() {
+        let rect = |x| Rect::new(x, 0, 1, 1).unwrap();
+        let xs = |rects: Option<Vec<Rect>>| {
+            rects.map(|rects| rects.iter().map(|r| r.x).collect::<Vec<_>>())
+        };
+
+        let mut tracker = DirtyTracker::new(2);
+        assert_eq!(xs(tracker.rects_for_age(1)), None);
+
+        tracker.record(&[rect(0)]);
+        tracker.record(&[rect(1), rect(2)]);
+        tracker.record(&[rect(3)]);
+        assert_eq!(xs(tracker.rects_for_age(0)), None);
+        assert_eq!(xs(tracker.rects_for_age(1)), Some(vec![3]));
+        // The first frame was forgotten.
+        assert_eq!(xs(tracker.rects_for_age(2)), Some(vec![1, 2, 3]));
+        assert_eq!(xs(tracker.rects_for_age(3)), None);
+
+        tracker.clear();
+        assert_eq!(xs(tracker.rects_for_age(1)), None);
+    }
+
+    #[test]
+    fn test_blend_row() {
+        let dst = [0x00_ff_00_00, 0x00_00_00_00, 0x00_20_40_60];
+        let blend = |src: [u32; 3], mode| {
+            let mut dst = dst;
+            blend_row(&mut dst, &src, mode);
+            dst
+        };
+
+        // Fully transparent and fully opaque pixels.
+        let src = [0x00_00_ff_00, 0xff_12_34_56, 0x00_ff_ff_ff];
+        assert_eq!(
+            blend(src, AlphaMode::Straight),
+            [0x00_ff_00_00, 0x00_12_34_56, 0x00_20_40_60]
+        );
+
+        // Half transparent white, which is `0x80_80_80_80` when premultiplied.
+        assert_eq!(
+            blend([0x80_ff_ff_ff; 3], AlphaMode::Straight),
+            [0x00_ff_80_80, 0x00_80_80_80, 0x00_90_a0_b0]
+        );
+        assert_eq!(
+            blend([0x80_80_80_80; 3], AlphaMode::Premultiplied),
+            [0x00_ff_80_80, 0x00_80_80_80, 0x00_90_a0_b0]
+        );
+
+        // Colors larger than the alpha saturate.
+        assert_eq!(
+            blend([0x00_ff_ff_ff; 3], AlphaMode::Premultiplied),
+            [0x00_ff_ff_ff; 3]
+        );
+    }
+
+    #[test]
+    fn test_copy_overlapping() {
+        let src = [1, 2, 3, 4, 5, 6];
+
+        // Grow from 3x2 to 4x3.
+        let mut dst = [9; 12];
+        copy_overlapping(&src, 3, &mut dst, 4);
+        assert_eq!(dst, [1, 2, 3, 0, 4, 5, 6, 0, 0, 0, 0, 0]);
+
+        // Shrink from 3x2 to 2x1.
+        let mut dst = [9; 2];
+        copy_overlapping(&src, 3, &mut dst, 2);
+        assert_eq!(dst, [1, 2]);
+    }
 }